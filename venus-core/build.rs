@@ -8,14 +8,18 @@ macro_rules! warn {
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut protos = vec![];
     for entry in WalkDir::new("proto") {
         let entry = entry?;
         if entry.clone().file_type().is_dir() {
             continue;
         }
-        tonic_build::configure()
-            .build_server(false)
-            .compile_protos(&[entry.path().to_str().unwrap()], &["proto"])?;
+        protos.push(entry.into_path());
     }
+    // Packages like `v2ray.core.common.protocol` span multiple files,
+    // compile them together so each generated package module is complete.
+    tonic_build::configure()
+        .build_server(false)
+        .compile_protos(&protos, &["proto"])?;
     Ok(())
 }
@@ -0,0 +1,235 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+use crate::config::error::{ConfigError, ConfigResult};
+
+/// Timestamp suffix of backup files, sortable by name
+const BACKUP_TIME_FORMAT: &str = "%Y%m%d%H%M%S%3f";
+
+/// Which config file a backup belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackupKind {
+    /// Venus config `config.toml`
+    Venus,
+    /// v2ray core config `config.json`
+    Core,
+}
+
+/// A backup file in the backup folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backup {
+    /// File name, e.g. `config.json.20250102150405123`
+    pub name: String,
+    pub kind: BackupKind,
+    pub created_at: DateTime<Utc>,
+    /// bytes
+    pub size: u64,
+}
+
+/// Backups live in `backups/` next to the config file
+pub fn backup_dir(path: &Path) -> PathBuf {
+    path.parent().unwrap_or(Path::new(".")).join("backups")
+}
+
+/// Write the file through a temp file, fsync it and rename it into place
+///
+/// A crash leaves either the old or the new content, never a truncated file.
+/// The temp file is private to this call and created owner-only, a replaced
+/// file keeps its own permissions.
+pub fn write_atomic(path: &Path, content: &[u8]) -> io::Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    let mut temp = NamedTempFile::new_in(dir)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        temp.as_file().set_permissions(metadata.permissions())?;
+    }
+    temp.write_all(content)?;
+    temp.as_file().sync_all()?;
+    temp.persist(path).map_err(|err| err.error)?;
+
+    // persist the rename itself
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Back up the current file then write the new content atomically
+///
+/// Nothing is backed up when the content did not change. Only the newest
+/// `keep` backups of this file are kept.
+pub fn write_with_backup(path: &Path, content: &[u8], keep: usize) -> io::Result<()> {
+    match fs::read(path) {
+        Ok(current) if current == content => return Ok(()),
+        Ok(current) if !current.is_empty() => {
+            backup(path, &current)?;
+            prune_backups(path, keep)?;
+        }
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    write_atomic(path, content)
+}
+
+/// Keep the file as it was before a schema migration
+///
+/// Stored as `backups/<file>.schema-v<from>`, it is not rotated.
+pub fn backup_before_migration(path: &Path, content: &[u8], from: u32) -> io::Result<PathBuf> {
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let backup_path = dir.join(format!("{file_name}.schema-v{from}"));
+    write_atomic(&backup_path, content)?;
+    Ok(backup_path)
+}
+
+/// Store `content` as a timestamped backup of `path`
+fn backup(path: &Path, content: &[u8]) -> io::Result<PathBuf> {
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir)?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut backup_path = dir.join(format!(
+        "{file_name}.{}",
+        Utc::now().format(BACKUP_TIME_FORMAT)
+    ));
+    // two writes in the same millisecond
+    while backup_path.exists() {
+        backup_path.as_mut_os_string().push("0");
+    }
+    write_atomic(&backup_path, content)?;
+    Ok(backup_path)
+}
+
+/// Backup file names of `path`, oldest first
+fn backup_names(path: &Path) -> io::Result<Vec<(String, DateTime<Utc>)>> {
+    let dir = backup_dir(path);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{file_name}.");
+    let mut names = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let stamp = name.strip_prefix(&prefix)?;
+            let created_at =
+                NaiveDateTime::parse_from_str(stamp.get(..17)?, BACKUP_TIME_FORMAT).ok()?;
+            Some((name, created_at.and_utc()))
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+/// Remove the oldest backups of `path` beyond `keep`
+fn prune_backups(path: &Path, keep: usize) -> io::Result<()> {
+    let names = backup_names(path)?;
+    let dir = backup_dir(path);
+    for (name, _) in names.iter().take(names.len().saturating_sub(keep)) {
+        fs::remove_file(dir.join(name))?;
+    }
+    Ok(())
+}
+
+/// Backups of `path`, newest first
+pub fn list_backups(path: &Path, kind: BackupKind) -> ConfigResult<Vec<Backup>> {
+    let dir = backup_dir(path);
+    let mut backups = backup_names(path)?
+        .into_iter()
+        .map(|(name, created_at)| {
+            let size = fs::metadata(dir.join(&name))
+                .map(|m| m.len())
+                .unwrap_or_default();
+            Backup {
+                name,
+                kind,
+                created_at,
+                size,
+            }
+        })
+        .collect::<Vec<_>>();
+    backups.reverse();
+    Ok(backups)
+}
+
+/// Content of the backup `name` of `path`
+///
+/// Only names listed for `path` are read, never arbitrary paths.
+pub fn read_backup(path: &Path, name: &str) -> ConfigResult<Vec<u8>> {
+    let exists = backup_names(path)?.iter().any(|(n, _)| n == name);
+    if !exists {
+        return Err(ConfigError::BackupNotFound(name.to_string()));
+    }
+    Ok(fs::read(backup_dir(path).join(name))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_write_with_backup_rotates() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        for i in 0..5 {
+            write_with_backup(&path, format!("{{\"v\":{i}}}").as_bytes(), 3).unwrap();
+        }
+        // unchanged content is not backed up
+        write_with_backup(&path, b"{\"v\":4}", 3).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"v\":4}");
+        let backups = list_backups(&path, BackupKind::Core).unwrap();
+        assert_eq!(backups.len(), 3);
+        let newest = fs::read_to_string(backup_dir(&path).join(&backups[0].name)).unwrap();
+        assert_eq!(newest, "{\"v\":3}");
+        // only the file and the backup folder are left
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_atomic_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        write_atomic(&path, b"a").unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        write_atomic(&path, b"b").unwrap();
+        assert_eq!(mode(&path), 0o640);
+    }
+
+    #[test]
+    fn test_read_backup() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        write_with_backup(&path, b"a", 10).unwrap();
+        write_with_backup(&path, b"b", 10).unwrap();
+
+        let backup = list_backups(&path, BackupKind::Venus).unwrap().remove(0);
+        assert_eq!(read_backup(&path, &backup.name).unwrap(), b"a");
+
+        assert!(matches!(
+            read_backup(&path, "../config.toml"),
+            Err(ConfigError::BackupNotFound(_))
+        ));
+    }
+}
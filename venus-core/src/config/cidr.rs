@@ -0,0 +1,91 @@
+use std::net::IpAddr;
+
+/// An IP network, `addr/prefix`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    pub addr: IpAddr,
+    pub prefix: u32,
+}
+
+impl Cidr {
+    /// Address length, 32 or 128
+    pub fn bits(&self) -> u32 {
+        if self.addr.is_ipv4() {
+            32
+        } else {
+            128
+        }
+    }
+
+    /// First and last address, IPv4 in the low 32 bits
+    pub fn range(&self) -> (u128, u128) {
+        let (value, bits) = match self.addr {
+            IpAddr::V4(ip) => (u32::from(ip) as u128, 32),
+            IpAddr::V6(ip) => (u128::from(ip), 128),
+        };
+        let host_bits = bits - self.prefix.min(bits);
+        let host_mask = u128::MAX.checked_shr(128 - host_bits).unwrap_or(0);
+        (value & !host_mask, value | host_mask)
+    }
+
+    /// Number of addresses, saturated at `u128::MAX`
+    pub fn size(&self) -> u128 {
+        let (start, end) = self.range();
+        (end - start).saturating_add(1)
+    }
+
+    /// `ip` is in this network, never across IPv4 and IPv6
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        if ip.is_ipv4() != self.addr.is_ipv4() {
+            return false;
+        }
+        let value = match ip {
+            IpAddr::V4(ip) => u32::from(ip) as u128,
+            IpAddr::V6(ip) => u128::from(ip),
+        };
+        let (start, end) = self.range();
+        (start..=end).contains(&value)
+    }
+}
+
+/// Parse `addr/prefix`, a single address is a network of itself
+///
+/// # Returns
+/// * `None` when the address or the prefix is invalid
+pub fn parse_cidr(cidr: &str) -> Option<Cidr> {
+    let (addr, prefix) = match cidr.trim().split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix)),
+        None => (cidr.trim(), None),
+    };
+    let addr = addr.trim().parse::<IpAddr>().ok()?;
+    let bits = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix = match prefix {
+        Some(prefix) => prefix.trim().parse::<u32>().ok().filter(|p| *p <= bits)?,
+        None => bits,
+    };
+    Some(Cidr { addr, prefix })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cidr() {
+        let cidr = parse_cidr("10.1.2.3/8").unwrap();
+        assert_eq!(cidr.range(), (0x0a00_0000, 0x0aff_ffff));
+        assert_eq!(cidr.size(), 1 << 24);
+        assert!(cidr.contains("10.255.0.1".parse().unwrap()));
+        assert!(!cidr.contains("11.0.0.1".parse().unwrap()));
+        assert!(!cidr.contains("::a01:203".parse().unwrap()));
+
+        let host = parse_cidr(" ::1 ").unwrap();
+        assert_eq!(host.prefix, 128);
+        assert_eq!(host.size(), 1);
+        assert_eq!(parse_cidr("::/0").unwrap().size(), u128::MAX);
+
+        for cidr in ["10.0.0.0/33", "10.0.0.0/", "example.com", "fc00::/129"] {
+            assert_eq!(parse_cidr(cidr), None, "{cidr}");
+        }
+    }
+}
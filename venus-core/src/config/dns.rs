@@ -0,0 +1,466 @@
+use std::{borrow::Cow, collections::BTreeMap, net::IpAddr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    cidr::parse_cidr,
+    error::{ConfigError, ConfigResult},
+    types::{CoreConfig, Dns, DnsServer, DnsServerObject, FakeDns, HostAddress},
+};
+
+const FAKEDNS: &str = "fakedns";
+const LOCALHOST: &str = "localhost";
+const DEFAULT_FAKEDNS_POOL: &str = "198.18.0.0/15";
+const DEFAULT_FAKEDNS_POOL_SIZE: u32 = 65535;
+
+/// Transport of a DNS server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsProtocol {
+    #[default]
+    Udp,
+    Tcp,
+    /// DNS over HTTPS
+    Doh,
+    /// DNS over TLS
+    Dot,
+    /// DNS over QUIC
+    Doq,
+    /// Answer with IPs from the fake DNS pool
+    Fakedns,
+    /// Resolver of the system
+    Localhost,
+}
+
+impl DnsProtocol {
+    fn scheme(self) -> Option<&'static str> {
+        match self {
+            DnsProtocol::Tcp => Some("tcp"),
+            DnsProtocol::Doh => Some("https"),
+            DnsProtocol::Dot => Some("tls"),
+            DnsProtocol::Doq => Some("quic"),
+            _ => None,
+        }
+    }
+}
+
+/// `queryStrategy` of the built-in DNS
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QueryStrategy {
+    #[default]
+    #[serde(rename = "UseIP")]
+    UseIp,
+    #[serde(rename = "UseIPv4")]
+    UseIpv4,
+    #[serde(rename = "UseIPv6")]
+    UseIpv6,
+}
+
+impl QueryStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QueryStrategy::UseIp => "UseIP",
+            QueryStrategy::UseIpv4 => "UseIPv4",
+            QueryStrategy::UseIpv6 => "UseIPv6",
+        }
+    }
+}
+
+/// A DNS server as shown in settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsServerEntry {
+    pub protocol: DnsProtocol,
+    /// IP or host, `host[:port]/path` for `doh`, empty for `fakedns` and `localhost`
+    pub address: String,
+    /// The protocol's default port when empty, not used by `doh`
+    pub port: Option<u16>,
+    /// Query directly instead of through the proxy, always on for `doq`
+    pub local: bool,
+    /// Domains this server is preferred for, e.g. `geosite:cn`
+    pub domains: Vec<String>,
+    /// Answers outside these IPs or `geoip:` categories are dropped
+    pub expect_ips: Vec<String>,
+    pub skip_fallback: bool,
+}
+
+/// Fake DNS pool, its IPs map back to the queried domains when sniffing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FakeDnsSetting {
+    pub enabled: bool,
+    /// CIDR of the pool
+    pub ip_pool: String,
+    pub pool_size: u32,
+}
+
+impl Default for FakeDnsSetting {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ip_pool: DEFAULT_FAKEDNS_POOL.into(),
+            pool_size: DEFAULT_FAKEDNS_POOL_SIZE,
+        }
+    }
+}
+
+/// Built-in DNS setting
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsSetting {
+    /// Queried in order
+    pub servers: Vec<DnsServerEntry>,
+    /// Static records, e.g. `domain:example.com` to IPs or a domain
+    pub hosts: BTreeMap<String, Vec<String>>,
+    pub query_strategy: QueryStrategy,
+    pub fakedns: FakeDnsSetting,
+}
+
+fn invalid(message: impl Into<String>) -> ConfigError {
+    ConfigError::InvalidDns(message.into())
+}
+
+/// Split `host:port`, IPv6 hosts need brackets to carry a port
+fn split_port(address: &str) -> (&str, Option<u16>) {
+    if let Some((host, port)) = address.rsplit_once(':') {
+        let bracketed = host.starts_with('[') && host.ends_with(']');
+        if let (true, Ok(port)) = (bracketed || !host.contains(':'), port.parse()) {
+            return (
+                host.trim_start_matches('[').trim_end_matches(']'),
+                Some(port),
+            );
+        }
+    }
+    (address, None)
+}
+
+fn to_server(entry: &DnsServerEntry) -> ConfigResult<DnsServer> {
+    let address = entry.address.trim();
+    let check = || {
+        if address.is_empty() || address.contains("://") || address.contains(char::is_whitespace) {
+            return Err(invalid(format!(
+                "invalid {:?} server {address}",
+                entry.protocol
+            )));
+        }
+        Ok(())
+    };
+    let mut port = None;
+    let address = match entry.protocol {
+        DnsProtocol::Fakedns => FAKEDNS.to_string(),
+        DnsProtocol::Localhost => LOCALHOST.to_string(),
+        DnsProtocol::Udp => {
+            address
+                .parse::<IpAddr>()
+                .map_err(|_| invalid(format!("udp server {address} is not an IP")))?;
+            port = entry.port;
+            address.to_string()
+        }
+        DnsProtocol::Doh => {
+            check()?;
+            if entry.port.is_some() {
+                return Err(invalid("put the doh port in its address"));
+            }
+            let local = if entry.local { "+local" } else { "" };
+            format!("https{local}://{address}")
+        }
+        protocol => {
+            check()?;
+            // the core only queries DoQ directly
+            let local = if entry.local || protocol == DnsProtocol::Doq {
+                "+local"
+            } else {
+                ""
+            };
+            let scheme = protocol.scheme().unwrap_or_default();
+            match entry.port {
+                Some(port) if address.contains(':') => {
+                    format!("{scheme}{local}://[{address}]:{port}")
+                }
+                Some(port) => format!("{scheme}{local}://{address}:{port}"),
+                None => format!("{scheme}{local}://{address}"),
+            }
+        }
+    };
+    let trimmed = |values: &[String]| {
+        values
+            .iter()
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+            .map(|value| Cow::Owned(value.to_string()))
+            .collect::<Vec<_>>()
+    };
+    let domains = trimmed(&entry.domains);
+    let expect_ips = trimmed(&entry.expect_ips);
+    if port.is_none() && domains.is_empty() && expect_ips.is_empty() && !entry.skip_fallback {
+        return Ok(DnsServer::Address(address.into()));
+    }
+    Ok(DnsServer::Server(DnsServerObject {
+        address: address.into(),
+        port,
+        domains,
+        expect_ips,
+        skip_fallback: entry.skip_fallback.then_some(true),
+    }))
+}
+
+fn from_server(server: &DnsServer) -> DnsServerEntry {
+    let (address, mut entry) = match server {
+        DnsServer::Address(address) => (address.as_ref(), DnsServerEntry::default()),
+        DnsServer::Server(server) => (
+            server.address.as_ref(),
+            DnsServerEntry {
+                port: server.port,
+                domains: server.domains.iter().map(|d| d.to_string()).collect(),
+                expect_ips: server.expect_ips.iter().map(|ip| ip.to_string()).collect(),
+                skip_fallback: server.skip_fallback.unwrap_or_default(),
+                ..DnsServerEntry::default()
+            },
+        ),
+    };
+    let Some((scheme, rest)) = address.split_once("://") else {
+        entry.protocol = match address {
+            FAKEDNS => DnsProtocol::Fakedns,
+            LOCALHOST => DnsProtocol::Localhost,
+            _ => {
+                entry.address = address.to_string();
+                DnsProtocol::Udp
+            }
+        };
+        return entry;
+    };
+    let (scheme, local) = match scheme.strip_suffix("+local") {
+        Some(scheme) => (scheme, true),
+        None => (scheme, false),
+    };
+    entry.local = local;
+    entry.protocol = match scheme {
+        "https" => DnsProtocol::Doh,
+        "tcp" => DnsProtocol::Tcp,
+        "tls" => DnsProtocol::Dot,
+        "quic" => DnsProtocol::Doq,
+        // unknown schemes are kept as they are
+        _ => {
+            entry.address = address.to_string();
+            return entry;
+        }
+    };
+    if entry.protocol == DnsProtocol::Doh {
+        entry.address = rest.to_string();
+    } else {
+        let (host, port) = split_port(rest);
+        entry.address = host.to_string();
+        entry.port = port;
+    }
+    entry
+}
+
+fn check_pool(setting: &FakeDnsSetting) -> ConfigResult<()> {
+    let pool = setting.ip_pool.trim();
+    let capacity = parse_cidr(pool)
+        .ok_or_else(|| invalid(format!("fake dns pool {pool} is not a CIDR")))?
+        .size();
+    if setting.pool_size == 0 || setting.pool_size as u128 > capacity {
+        return Err(invalid(format!(
+            "fake dns pool size should be 1 to {capacity}"
+        )));
+    }
+    Ok(())
+}
+
+/// Add or remove `fakedns` in a sniffing destination list
+fn set_fakedns_override(destinations: &mut Vec<Cow<'static, str>>, enabled: bool) {
+    destinations.retain(|destination| destination != FAKEDNS);
+    if enabled {
+        destinations.push(FAKEDNS.into());
+    }
+}
+
+impl CoreConfig {
+    /// Destinations new sniffing settings override,
+    /// with `fakedns` while the fake DNS pool is on
+    pub(crate) fn sniff_destinations(&self) -> Vec<Cow<'static, str>> {
+        let mut destinations = vec!["http".into(), "tls".into()];
+        set_fakedns_override(&mut destinations, self.fakedns.is_some());
+        destinations
+    }
+
+    /// Current DNS setting
+    pub fn dns_setting(&self) -> DnsSetting {
+        let dns = self.dns.clone().unwrap_or_default();
+        let query_strategy = match dns.query_strategy.as_deref() {
+            Some("UseIPv4") => QueryStrategy::UseIpv4,
+            Some("UseIPv6") => QueryStrategy::UseIpv6,
+            _ => QueryStrategy::UseIp,
+        };
+        let fakedns = match self.fakedns.as_ref().and_then(|pools| pools.first()) {
+            Some(pool) => FakeDnsSetting {
+                enabled: true,
+                ip_pool: pool.ip_pool.to_string(),
+                pool_size: pool.pool_size,
+            },
+            None => FakeDnsSetting::default(),
+        };
+        DnsSetting {
+            servers: dns.servers.iter().map(from_server).collect(),
+            hosts: dns
+                .hosts
+                .into_iter()
+                .map(|(domain, address)| {
+                    let addresses = match address {
+                        HostAddress::One(address) => vec![address.to_string()],
+                        HostAddress::Many(addresses) => {
+                            addresses.iter().map(|a| a.to_string()).collect()
+                        }
+                    };
+                    (domain, addresses)
+                })
+                .collect(),
+            query_strategy,
+            fakedns,
+        }
+    }
+
+    /// Replace the DNS servers, hosts and fake DNS pool
+    ///
+    /// A `fakedns` server is put first when the pool is on, and sniffing
+    /// inbounds override destinations with `fakedns` while it is.
+    pub fn set_dns(&mut self, setting: &DnsSetting) -> ConfigResult<()> {
+        let fakedns = setting.fakedns.enabled;
+        if fakedns {
+            check_pool(&setting.fakedns)?;
+        }
+        let mut servers = vec![];
+        for entry in &setting.servers {
+            if entry.protocol == DnsProtocol::Fakedns && !fakedns {
+                return Err(invalid("enable the fake dns pool to use a fakedns server"));
+            }
+            servers.push(to_server(entry)?);
+        }
+        if fakedns
+            && !servers
+                .iter()
+                .any(|server| from_server(server).protocol == DnsProtocol::Fakedns)
+        {
+            servers.insert(0, DnsServer::Address(FAKEDNS.into()));
+        }
+        let mut hosts = BTreeMap::new();
+        for (domain, addresses) in &setting.hosts {
+            let domain = domain.trim();
+            let mut addresses = addresses
+                .iter()
+                .map(|address| address.trim())
+                .filter(|address| !address.is_empty())
+                .map(|address| Cow::Owned(address.to_string()))
+                .collect::<Vec<_>>();
+            if domain.is_empty() || addresses.is_empty() {
+                return Err(invalid(format!(
+                    "host {domain} needs a domain and an address"
+                )));
+            }
+            let address = match addresses.len() {
+                1 => HostAddress::One(addresses.remove(0)),
+                _ => HostAddress::Many(addresses),
+            };
+            hosts.insert(domain.to_string(), address);
+        }
+
+        let mut dns = self.dns.take().unwrap_or_default();
+        dns.servers = servers;
+        dns.hosts = hosts;
+        dns.query_strategy = match setting.query_strategy {
+            QueryStrategy::UseIp => None,
+            strategy => Some(strategy.as_str().into()),
+        };
+        self.dns = (dns != Dns::default()).then_some(dns);
+        self.fakedns = fakedns.then(|| {
+            vec![FakeDns {
+                ip_pool: setting.fakedns.ip_pool.trim().to_string().into(),
+                pool_size: setting.fakedns.pool_size,
+            }]
+        });
+        let sniffings = self
+            .inbounds
+            .iter_mut()
+            .chain(self.inbound_detour.iter_mut())
+            .filter_map(|inbound| inbound.sniffing.as_mut());
+        for sniffing in sniffings {
+            set_fakedns_override(&mut sniffing.dest_override, fakedns);
+        }
+        let tun = self
+            .services
+            .as_mut()
+            .and_then(|services| services.tun.as_mut());
+        if let Some(sniffing) = tun.and_then(|tun| tun.sniffing_settings.as_mut()) {
+            set_fakedns_override(&mut sniffing.destination_override, fakedns);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::raw::default_core;
+
+    #[test]
+    fn test_dns_server_address() {
+        for address in [
+            "1.1.1.1",
+            "https://dns.google/dns-query",
+            "https+local://1.1.1.1:8443/dns-query",
+            "tcp://8.8.8.8:53",
+            "tls://dns.google",
+            "quic+local://[2606:4700::1111]:853",
+            "fakedns",
+            "localhost",
+        ] {
+            let server = DnsServer::Address(address.into());
+            assert_eq!(to_server(&from_server(&server)).unwrap(), server);
+        }
+        let entry = from_server(&DnsServer::Address("tls+local://1.1.1.1:853".into()));
+        assert_eq!(entry.protocol, DnsProtocol::Dot);
+        assert_eq!((entry.address.as_str(), entry.port), ("1.1.1.1", Some(853)));
+        assert!(entry.local);
+
+        let invalid = DnsServerEntry {
+            protocol: DnsProtocol::Udp,
+            address: "dns.google".into(),
+            ..DnsServerEntry::default()
+        };
+        assert!(to_server(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_set_dns() {
+        let mut core = default_core();
+        let mut setting = core.dns_setting();
+        assert_eq!(setting.servers[1].domains, ["geosite:cn"]);
+        assert_eq!(setting.servers[1].port, Some(53));
+        assert_eq!(setting.servers[3].protocol, DnsProtocol::Localhost);
+
+        core.set_dns(&setting).unwrap();
+        assert_eq!(core.dns_setting(), setting);
+
+        setting.fakedns.enabled = true;
+        core.set_dns(&setting).unwrap();
+        let updated = core.dns_setting();
+        assert_eq!(updated.servers[0].protocol, DnsProtocol::Fakedns);
+        assert_eq!(updated.fakedns, setting.fakedns);
+        let json = serde_json::to_value(&core).unwrap();
+        assert_eq!(json["fakedns"][0]["ipPool"], DEFAULT_FAKEDNS_POOL);
+        assert!(core.sniff_destinations().contains(&FAKEDNS.into()));
+
+        let mut disabled = updated;
+        disabled.fakedns.enabled = false;
+        assert!(core.set_dns(&disabled).is_err());
+        disabled.servers.remove(0);
+        core.set_dns(&disabled).unwrap();
+        assert_eq!(core.fakedns, None);
+        assert!(core
+            .inbounds
+            .iter()
+            .filter_map(|inbound| inbound.sniffing.as_ref())
+            .all(|sniffing| !sniffing.dest_override.contains(&FAKEDNS.into())));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{borrow::Cow, io};
+use std::{borrow::Cow, io, path::PathBuf};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ConfigError {
@@ -15,6 +15,32 @@ pub enum ConfigError {
 
     #[error("{0}")]
     Empty(Cow<'static, str>),
+    #[error("backup {0} not found")]
+    BackupNotFound(String),
+    #[error("config schema {0} is newer than this venus supports")]
+    UnsupportedSchema(u32),
+    #[error("config snapshot {0} not found")]
+    SnapshotNotFound(u64),
+    #[error("{0:?} was edited outside venus, reload or overwrite it first")]
+    Conflict(PathBuf),
+    #[error("invalid routing rule: {0}")]
+    InvalidRule(String),
+    #[error("routing rule {0} not found")]
+    RuleNotFound(usize),
+    #[error("invalid inbound: {0}")]
+    InvalidInbound(String),
+    #[error("inbound {0} not found")]
+    InboundNotFound(String),
+    #[error("invalid dns setting: {0}")]
+    InvalidDns(String),
+    #[error("invalid policy: {0}")]
+    InvalidPolicy(String),
+    #[error("line {line} column {column}: {message}")]
+    InvalidCore {
+        line: usize,
+        column: usize,
+        message: String,
+    },
 }
 
 pub type ConfigResult<T, E = ConfigError> = Result<T, E>;
@@ -0,0 +1,348 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{
+    backup::write_atomic,
+    error::{ConfigError, ConfigResult},
+    types::{CoreConfig, VenusConfig},
+};
+
+/// A recorded state of venus and core config
+///
+/// `venus.user` is never recorded, reverting does not change credentials.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub id: u64,
+    pub created_at: DateTime<Utc>,
+    /// JWT `sub` of who made the change
+    pub author: String,
+    pub reason: String,
+    pub venus: VenusConfig,
+    pub core: Option<CoreConfig>,
+}
+
+/// Snapshot without the configs, for listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    pub id: u64,
+    pub created_at: DateTime<Utc>,
+    pub author: String,
+    pub reason: String,
+}
+
+impl From<&Snapshot> for SnapshotInfo {
+    fn from(snapshot: &Snapshot) -> Self {
+        Self {
+            id: snapshot.id,
+            created_at: snapshot.created_at,
+            author: snapshot.author.clone(),
+            reason: snapshot.reason.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffOp {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// One changed value between two JSON documents
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffEntry {
+    /// JSON pointer of the value, e.g. `/core/routing/rules/0/outboundTag`
+    pub path: String,
+    pub op: DiffOp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub old: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new: Option<Value>,
+}
+
+/// History lives in `history/` next to the venus config
+pub fn history_dir(venus_path: &Path) -> PathBuf {
+    venus_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .join("history")
+}
+
+fn snapshot_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{id:08}.json"))
+}
+
+/// Snapshot ids in `dir`, oldest first
+fn snapshot_ids(dir: &Path) -> ConfigResult<Vec<u64>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut ids = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_suffix(".json")?.parse::<u64>().ok()
+        })
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+/// Load a snapshot by id
+pub fn load_snapshot(dir: &Path, id: u64) -> ConfigResult<Snapshot> {
+    let path = snapshot_path(dir, id);
+    if !path.exists() {
+        return Err(ConfigError::SnapshotNotFound(id));
+    }
+    let content = fs::read(path)?;
+    let mut snapshot = serde_json::from_slice::<Snapshot>(&content)?;
+    // files from before the user was left out, the password hash never leaves
+    snapshot.venus.user = None;
+    Ok(snapshot)
+}
+
+/// Recorded snapshots, newest first
+///
+/// Only the metadata is read, a snapshot whose configs no longer load
+/// is still listed. Unreadable files are reported, not skipped.
+pub fn list_snapshots(dir: &Path) -> ConfigResult<Vec<SnapshotInfo>> {
+    let mut snapshots = snapshot_ids(dir)?
+        .into_iter()
+        .map(|id| -> ConfigResult<SnapshotInfo> {
+            let content = fs::read(snapshot_path(dir, id))?;
+            Ok(serde_json::from_slice(&content)?)
+        })
+        .collect::<ConfigResult<Vec<_>>>()?;
+    snapshots.reverse();
+    Ok(snapshots)
+}
+
+/// Record a snapshot when the configs differ from the latest one
+///
+/// Only the newest `keep` snapshots are kept.
+///
+/// # Returns
+/// * `None` if nothing changed
+pub fn record_snapshot(
+    dir: &Path,
+    venus: &VenusConfig,
+    core: Option<&CoreConfig>,
+    author: &str,
+    reason: &str,
+    keep: usize,
+) -> ConfigResult<Option<SnapshotInfo>> {
+    let mut venus = venus.clone();
+    venus.user = None;
+
+    let ids = snapshot_ids(dir)?;
+    if let Some(latest) = ids.last().and_then(|id| load_snapshot(dir, *id).ok()) {
+        let same_venus = serde_json::to_value(&latest.venus)? == serde_json::to_value(&venus)?;
+        let same_core = serde_json::to_value(&latest.core)? == serde_json::to_value(core)?;
+        if same_venus && same_core {
+            return Ok(None);
+        }
+    }
+
+    let snapshot = Snapshot {
+        id: ids.last().map_or(1, |id| id + 1),
+        created_at: Utc::now(),
+        author: author.to_string(),
+        reason: reason.to_string(),
+        venus,
+        core: core.cloned(),
+    };
+    fs::create_dir_all(dir)?;
+    let content = serde_json::to_vec_pretty(&snapshot)?;
+    write_atomic(&snapshot_path(dir, snapshot.id), &content)?;
+
+    for id in ids.iter().take((ids.len() + 1).saturating_sub(keep)) {
+        fs::remove_file(snapshot_path(dir, *id))?;
+    }
+    Ok(Some(SnapshotInfo::from(&snapshot)))
+}
+
+/// Structural diff of the configs of two snapshots
+///
+/// `venus.user` is left out, its password hash is never returned.
+pub fn diff_snapshots(from: &Snapshot, to: &Snapshot) -> ConfigResult<Vec<DiffEntry>> {
+    let value = |snapshot: &Snapshot| -> ConfigResult<Value> {
+        let mut venus = serde_json::to_value(&snapshot.venus)?;
+        if let Some(venus) = venus.as_object_mut() {
+            venus.remove("user");
+        }
+        Ok(serde_json::json!({
+            "venus": venus,
+            "core": serde_json::to_value(&snapshot.core)?,
+        }))
+    };
+    let mut entries = vec![];
+    diff_value("", &value(from)?, &value(to)?, &mut entries);
+    Ok(entries)
+}
+
+/// Escape a key for a JSON pointer
+fn pointer_key(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+/// Compare objects by key and arrays by index, anything else by value
+pub fn diff_value(path: &str, old: &Value, new: &Value, entries: &mut Vec<DiffEntry>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                let path = format!("{path}/{}", pointer_key(key));
+                match new.get(key) {
+                    Some(new_value) => diff_value(&path, old_value, new_value, entries),
+                    None => entries.push(DiffEntry {
+                        path,
+                        op: DiffOp::Removed,
+                        old: Some(old_value.clone()),
+                        new: None,
+                    }),
+                }
+            }
+            for (key, new_value) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+                entries.push(DiffEntry {
+                    path: format!("{path}/{}", pointer_key(key)),
+                    op: DiffOp::Added,
+                    old: None,
+                    new: Some(new_value.clone()),
+                });
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                let path = format!("{path}/{i}");
+                match (old.get(i), new.get(i)) {
+                    (Some(old_value), Some(new_value)) => {
+                        diff_value(&path, old_value, new_value, entries)
+                    }
+                    (Some(old_value), None) => entries.push(DiffEntry {
+                        path,
+                        op: DiffOp::Removed,
+                        old: Some(old_value.clone()),
+                        new: None,
+                    }),
+                    (None, Some(new_value)) => entries.push(DiffEntry {
+                        path,
+                        op: DiffOp::Added,
+                        old: None,
+                        new: Some(new_value.clone()),
+                    }),
+                    (None, None) => {}
+                }
+            }
+        }
+        (old, new) if old != new => entries.push(DiffEntry {
+            path: path.to_string(),
+            op: DiffOp::Changed,
+            old: Some(old.clone()),
+            new: Some(new.clone()),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_diff_value() {
+        let old = json!({"a": 1, "b": {"c": [1, 2]}, "d/e": true});
+        let new = json!({"a": 2, "b": {"c": [1]}, "f": null});
+        let mut entries = vec![];
+        diff_value("", &old, &new, &mut entries);
+
+        let ops = entries
+            .iter()
+            .map(|e| (e.path.as_str(), e.op))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            vec![
+                ("/a", DiffOp::Changed),
+                ("/b/c/1", DiffOp::Removed),
+                ("/d~1e", DiffOp::Removed),
+                ("/f", DiffOp::Added),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_snapshot() {
+        let dir = tempdir().unwrap();
+        let mut venus = VenusConfig::default();
+        let first = record_snapshot(dir.path(), &venus, None, "admin", "init", 2).unwrap();
+        assert_eq!(first.unwrap().id, 1);
+        // unchanged
+        assert!(
+            record_snapshot(dir.path(), &venus, None, "admin", "noop", 2)
+                .unwrap()
+                .is_none()
+        );
+
+        venus.settings.current_id = "node".into();
+        record_snapshot(dir.path(), &venus, None, "admin", "select", 2).unwrap();
+        venus.settings.speed_url = "https://example.com".into();
+        record_snapshot(dir.path(), &venus, None, "admin", "speed", 2).unwrap();
+
+        let snapshots = list_snapshots(dir.path()).unwrap();
+        assert_eq!(
+            snapshots.iter().map(|s| s.id).collect::<Vec<_>>(),
+            vec![3, 2]
+        );
+        let diff = diff_snapshots(
+            &load_snapshot(dir.path(), 2).unwrap(),
+            &load_snapshot(dir.path(), 3).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].path, "/venus/settings/speedUrl");
+        assert!(matches!(
+            load_snapshot(dir.path(), 1),
+            Err(ConfigError::SnapshotNotFound(1))
+        ));
+    }
+
+    #[test]
+    fn test_load_old_schema_snapshot() {
+        let dir = tempdir().unwrap();
+        // recorded at schema 2, before `routingMode` and `lanAllowlist`
+        let snapshot = json!({
+            "id": 1,
+            "createdAt": "2025-01-01T00:00:00Z",
+            "author": "admin",
+            "reason": "init",
+            "venus": {
+                "version": "0.1.0",
+                "subscriptions": [],
+                "settings": {"speedUrl": "", "currentId": "", "logging": false},
+                "user": {"username": "admin", "password": "hash"},
+            },
+            "core": null,
+        });
+        fs::write(snapshot_path(dir.path(), 1), snapshot.to_string()).unwrap();
+
+        let loaded = load_snapshot(dir.path(), 1).unwrap();
+        assert!(loaded.venus.settings.lan_allowlist.is_empty());
+        assert!(loaded.venus.user.is_none());
+        assert_eq!(list_snapshots(dir.path()).unwrap().len(), 1);
+
+        fs::write(snapshot_path(dir.path(), 2), "{").unwrap();
+        assert!(list_snapshots(dir.path()).is_err());
+    }
+}
@@ -0,0 +1,341 @@
+use std::{borrow::Cow, net::IpAddr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{
+        cidr::parse_cidr,
+        error::{ConfigError, ConfigResult},
+        types::{Account, CoreConfig, Inbound, Rule, Sniffing},
+    },
+    consts::{BLOCKED_OUTBOUND_TAG, LAN_RULE_TAG},
+};
+
+/// Protocols of the local proxy inbounds managed here
+const PROXY_PROTOCOLS: [&str; 2] = ["socks", "http"];
+const LOCAL_LISTEN: &str = "127.0.0.1";
+const LAN_LISTEN: &str = "0.0.0.0";
+
+/// Changes to a proxy inbound, `None` fields are kept
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboundUpdate {
+    pub port: Option<u16>,
+    /// Listen IP, e.g. `127.0.0.1`
+    pub listen: Option<String>,
+    /// Username/password accounts, empty disables authentication
+    pub accounts: Option<Vec<Account>>,
+    /// UDP relay, socks only
+    pub udp: Option<bool>,
+    /// Sniff `http` and `tls` destinations
+    pub sniffing: Option<bool>,
+}
+
+/// Whether the proxy inbounds accept connections from other hosts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LanSetting {
+    pub allow: bool,
+    /// IPs or CIDRs allowed to connect, any host when empty.
+    /// Loopback is always allowed
+    pub allowlist: Vec<String>,
+}
+
+fn invalid(message: impl Into<String>) -> ConfigError {
+    ConfigError::InvalidInbound(message.into())
+}
+
+fn is_proxy(inbound: &Inbound) -> bool {
+    PROXY_PROTOCOLS.contains(&inbound.protocol.as_ref())
+}
+
+/// Smallest list of CIDRs covering `start..=end`
+fn range_cidrs(mut start: u128, end: u128, bits: u32) -> Vec<(u128, u32)> {
+    let mut cidrs = vec![];
+    loop {
+        let span = end - start;
+        // largest block aligned at `start` that ends before `end`
+        let mut size = start.trailing_zeros().min(bits);
+        while size > 0 && u128::MAX >> (128 - size) > span {
+            size -= 1;
+        }
+        cidrs.push((start, bits - size));
+        let last = if size == 0 {
+            start
+        } else {
+            start + (u128::MAX >> (128 - size))
+        };
+        if last >= end {
+            return cidrs;
+        }
+        start = last + 1;
+    }
+}
+
+/// CIDRs of the address space not covered by `ranges`
+fn complement(mut ranges: Vec<(u128, u128)>, bits: u32) -> Vec<(u128, u32)> {
+    let max = u128::MAX >> (128 - bits);
+    ranges.sort();
+    let mut cidrs = vec![];
+    let mut next = Some(0u128);
+    for (start, end) in ranges {
+        let Some(from) = next else {
+            break;
+        };
+        if start > from {
+            cidrs.extend(range_cidrs(from, start - 1, bits));
+        }
+        if end >= from {
+            next = end.checked_add(1).filter(|next| *next <= max);
+        }
+    }
+    if let Some(from) = next {
+        cidrs.extend(range_cidrs(from, max, bits));
+    }
+    cidrs
+}
+
+/// Sources outside `allowlist` and loopback, as rule `source` values
+fn blocked_sources(allowlist: &[String]) -> ConfigResult<Vec<Cow<'static, str>>> {
+    let (mut v4, mut v6) = (vec![], vec![]);
+    for cidr in allowlist
+        .iter()
+        .map(String::as_str)
+        .chain(["127.0.0.0/8", "::1"])
+    {
+        let cidr =
+            parse_cidr(cidr).ok_or_else(|| invalid(format!("{cidr} is not an IP or CIDR")))?;
+        match cidr.addr {
+            IpAddr::V4(_) => v4.push(cidr.range()),
+            IpAddr::V6(_) => v6.push(cidr.range()),
+        }
+    }
+    let v4 = complement(v4, 32)
+        .into_iter()
+        .map(|(ip, prefix)| format!("{}/{prefix}", IpAddr::from((ip as u32).to_be_bytes())));
+    let v6 = complement(v6, 128)
+        .into_iter()
+        .map(|(ip, prefix)| format!("{}/{prefix}", IpAddr::from(ip.to_be_bytes())));
+    Ok(v4.chain(v6).map(Cow::from).collect())
+}
+
+impl CoreConfig {
+    fn proxy_inbound_mut(&mut self, tag: &str) -> ConfigResult<&mut Inbound> {
+        self.inbounds
+            .iter_mut()
+            .find(|inbound| inbound.tag == tag && is_proxy(inbound))
+            .ok_or_else(|| ConfigError::InboundNotFound(tag.to_string()))
+    }
+
+    /// Change a socks or http inbound
+    pub fn update_inbound(&mut self, tag: &str, update: InboundUpdate) -> ConfigResult<()> {
+        // everything is checked before the inbound changes
+        if let Some(port) = update.port {
+            if port == 0 {
+                return Err(invalid("port can not be 0"));
+            }
+            let used = self
+                .inbounds
+                .iter()
+                .chain(self.inbound_detour.iter())
+                .find(|inbound| inbound.port == port && inbound.tag != tag);
+            if let Some(used) = used {
+                return Err(invalid(format!(
+                    "port {port} is used by inbound {}",
+                    used.tag
+                )));
+            }
+        }
+        if let Some(listen) = &update.listen {
+            listen
+                .parse::<IpAddr>()
+                .map_err(|_| invalid(format!("listen {listen} is not an IP")))?;
+        }
+        if let Some(accounts) = &update.accounts {
+            if accounts.iter().any(|account| account.user.is_empty()) {
+                return Err(invalid("account user can not be empty"));
+            }
+        }
+
+        let destinations = self.sniff_destinations();
+        let inbound = self.proxy_inbound_mut(tag)?;
+        if update.udp.is_some() && inbound.protocol != "socks" {
+            return Err(invalid(format!(
+                "{} inbound does not relay udp",
+                inbound.protocol
+            )));
+        }
+        if let Some(port) = update.port {
+            inbound.port = port;
+        }
+        if let Some(listen) = update.listen {
+            inbound.listen = Some(listen.into());
+        }
+        if let Some(accounts) = update.accounts {
+            // http inbounds authenticate whenever accounts are set
+            if inbound.protocol == "socks" {
+                let auth = if accounts.is_empty() {
+                    "noauth"
+                } else {
+                    "password"
+                };
+                inbound.settings.auth = Some(auth.into());
+            }
+            inbound.settings.accounts = (!accounts.is_empty()).then_some(accounts);
+        }
+        if let Some(udp) = update.udp {
+            inbound.settings.udp = Some(udp);
+        }
+        if let Some(enabled) = update.sniffing {
+            inbound
+                .sniffing
+                .get_or_insert_with(|| Sniffing {
+                    dest_override: destinations,
+                    ..Sniffing::default()
+                })
+                .enabled = enabled;
+        }
+        Ok(())
+    }
+
+    /// Any proxy inbound listens on all addresses
+    pub fn allow_lan(&self) -> bool {
+        self.inbounds.iter().filter(|i| is_proxy(i)).any(|inbound| {
+            matches!(
+                inbound.listen.as_deref(),
+                None | Some("0.0.0.0" | "::" | "")
+            )
+        })
+    }
+
+    /// Rule blocking proxy connections from sources outside `allowlist`
+    fn lan_rule(&self, allowlist: &[String]) -> ConfigResult<Option<Rule>> {
+        if allowlist.is_empty() {
+            return Ok(None);
+        }
+        let tags = self
+            .inbounds
+            .iter()
+            .filter(|i| is_proxy(i))
+            .map(|inbound| inbound.tag.clone())
+            .collect();
+        Ok(Some(Rule {
+            inbound_tag: Some(tags),
+            source: Some(blocked_sources(allowlist)?),
+            rule_tag: Some(LAN_RULE_TAG.into()),
+            ..Rule::new(BLOCKED_OUTBOUND_TAG.into())
+        }))
+    }
+
+    /// Open or close the proxy inbounds to the LAN
+    ///
+    /// With an allowlist a rule blocking other sources is put first,
+    /// replacing the one made before wherever it was moved.
+    pub fn set_lan(&mut self, lan: &LanSetting) -> ConfigResult<()> {
+        let rule = if lan.allow {
+            self.lan_rule(&lan.allowlist)?
+        } else {
+            None
+        };
+        self.routing
+            .rules
+            .retain(|rule| rule.rule_tag.as_deref() != Some(LAN_RULE_TAG));
+        if let Some(rule) = rule {
+            self.routing.rules.insert(0, rule);
+        }
+        let listen = if lan.allow { LAN_LISTEN } else { LOCAL_LISTEN };
+        for inbound in self.inbounds.iter_mut().filter(|i| is_proxy(i)) {
+            inbound.listen = Some(listen.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::raw::default_core;
+
+    #[test]
+    fn test_blocked_sources() {
+        let sources = blocked_sources(&["192.168.1.0/24".into()]).unwrap();
+        let v4 = sources
+            .iter()
+            .filter(|s| !s.contains(':'))
+            .collect::<Vec<_>>();
+        assert!(v4.contains(&&Cow::from("0.0.0.0/2")));
+        assert!(v4.contains(&&Cow::from("192.168.0.0/24")));
+        assert!(v4.contains(&&Cow::from("192.168.2.0/23")));
+        assert!(!v4
+            .iter()
+            .any(|s| s.starts_with("192.168.1.") || s.starts_with("127.")));
+        // ipv6 except ::1
+        assert!(sources.contains(&Cow::from("::/128")));
+        assert!(sources.contains(&Cow::from("::2/127")));
+        assert!(sources.contains(&Cow::from("8000::/1")));
+
+        assert_eq!(complement(vec![(0, u32::MAX as u128)], 32), vec![]);
+        assert!(blocked_sources(&["10.0.0.0/33".into()]).is_err());
+    }
+
+    #[test]
+    fn test_update_inbound() {
+        let mut core = default_core();
+        let update = InboundUpdate {
+            port: Some(1080),
+            accounts: Some(vec![Account {
+                user: "rua".into(),
+                pass: "pass".into(),
+            }]),
+            udp: Some(true),
+            ..InboundUpdate::default()
+        };
+        core.update_inbound("socks", update).unwrap();
+        let socks = &core.inbounds[0];
+        assert_eq!(socks.port, 1080);
+        assert_eq!(socks.settings.auth.as_deref(), Some("password"));
+        assert_eq!(socks.settings.udp, Some(true));
+
+        let taken = InboundUpdate {
+            port: Some(10809),
+            ..InboundUpdate::default()
+        };
+        assert!(core.update_inbound("socks", taken).is_err());
+        let udp = InboundUpdate {
+            udp: Some(true),
+            ..InboundUpdate::default()
+        };
+        assert!(core.update_inbound("http", udp).is_err());
+        assert!(matches!(
+            core.update_inbound("api", InboundUpdate::default()),
+            Err(ConfigError::InboundNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_lan() {
+        let mut core = default_core();
+        let rules = core.routing.rules.len();
+        let lan = LanSetting {
+            allow: true,
+            allowlist: vec!["192.168.1.0/24".into()],
+        };
+        core.set_lan(&lan).unwrap();
+        assert!(core.allow_lan());
+        assert_eq!(core.routing.rules.len(), rules + 1);
+        assert_eq!(core.routing.rules[0].outbound_tag, BLOCKED_OUTBOUND_TAG);
+        // found again after it was moved
+        core.routing.rules.rotate_left(1);
+        core.set_lan(&lan).unwrap();
+        assert_eq!(core.routing.rules.len(), rules + 1);
+
+        let open = LanSetting {
+            allow: true,
+            allowlist: vec![],
+        };
+        core.set_lan(&open).unwrap();
+        assert_eq!(core.routing.rules.len(), rules);
+        core.set_lan(&LanSetting::default()).unwrap();
+        assert!(!core.allow_lan());
+    }
+}
@@ -0,0 +1,326 @@
+use std::{borrow::Cow, net::IpAddr};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{
+        cidr::parse_cidr,
+        types::{CoreConfig, Rule},
+    },
+    geodata::GeoData,
+};
+
+/// A connection to route, like the core's `RoutingContext`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteQuery {
+    /// Domain or IP
+    pub target: String,
+    pub port: Option<u16>,
+    /// `tcp` or `udp`, defaults to `tcp`
+    pub network: Option<String>,
+    pub source: Option<IpAddr>,
+    pub inbound_tag: Option<String>,
+    /// Sniffed protocol, `http`, `tls` or `bittorrent`
+    pub protocol: Option<String>,
+    /// Inbound user email
+    pub user: Option<String>,
+}
+
+impl RouteQuery {
+    fn target_ip(&self) -> Option<IpAddr> {
+        self.target.parse().ok()
+    }
+
+    fn network(&self) -> &str {
+        self.network.as_deref().unwrap_or("tcp")
+    }
+}
+
+/// Which rule a query matches
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteMatch {
+    /// Index of the first matching rule, `None` when no rule matches
+    pub rule_index: Option<usize>,
+    pub rule: Option<Rule>,
+    /// Matched rule's outbound, or the first outbound when no rule matches
+    pub outbound_tag: Option<String>,
+    pub balancer_tag: Option<String>,
+    /// Rules before the match whose conditions cannot be checked offline,
+    /// e.g. `ext:` or `geosite:` without geo data, they are treated as not matching
+    pub unresolved: Vec<usize>,
+    /// Outbound reported by the core's `TestRoute`, when the core answered
+    pub core_outbound_tag: Option<String>,
+}
+
+/// Any value of a condition list matches
+///
+/// `None` when no value matches but some could not be checked.
+fn any_of(values: &[Cow<'static, str>], check: impl Fn(&str) -> Option<bool>) -> Option<bool> {
+    let mut unknown = false;
+    for value in values {
+        match check(value) {
+            Some(true) => return Some(true),
+            Some(false) => {}
+            None => unknown = true,
+        }
+    }
+    if unknown {
+        None
+    } else {
+        Some(false)
+    }
+}
+
+/// `domain:` matches the domain and its subdomains
+pub(crate) fn match_subdomain(domain: &str, pattern: &str) -> bool {
+    domain == pattern
+        || domain
+            .strip_suffix(pattern)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+pub(crate) fn match_domain(domain: &str, pattern: &str, geo: Option<&GeoData>) -> Option<bool> {
+    if let Some(category) = pattern.strip_prefix("geosite:") {
+        return geo?.site_contains(category, domain);
+    }
+    let domain = domain.to_lowercase();
+    let Some((prefix, value)) = pattern.split_once(':') else {
+        // plain string matches as a keyword
+        return Some(domain.contains(&pattern.to_lowercase()));
+    };
+    // the regexp decides its own case sensitivity
+    if prefix == "regexp" {
+        return Regex::new(value).ok().map(|re| re.is_match(&domain));
+    }
+    let value = value.to_lowercase();
+    match prefix {
+        "domain" => Some(match_subdomain(&domain, &value)),
+        "full" => Some(domain == value),
+        "keyword" => Some(domain.contains(&value)),
+        // needs geo data
+        _ => None,
+    }
+}
+
+pub(crate) fn match_cidr(ip: IpAddr, cidr: &str, geo: Option<&GeoData>) -> Option<bool> {
+    if let Some(category) = cidr.strip_prefix("geoip:") {
+        return geo?.ip_contains(category, ip);
+    }
+    if cidr.starts_with("ext:") {
+        return None;
+    }
+    Some(parse_cidr(cidr)?.contains(ip))
+}
+
+fn match_port(port: u16, ports: &str) -> bool {
+    ports.split(',').any(|part| {
+        let part = part.trim();
+        match part.split_once('-') {
+            Some((from, to)) => match (from.trim().parse::<u16>(), to.trim().parse::<u16>()) {
+                (Ok(from), Ok(to)) => (from..=to).contains(&port),
+                _ => false,
+            },
+            None => part.parse() == Ok(port),
+        }
+    })
+}
+
+/// Every condition of the rule matches
+///
+/// `None` when no condition fails but some could not be checked.
+/// `geosite:` and `geoip:` values are checked against `geo` when given.
+pub fn match_rule(rule: &Rule, query: &RouteQuery, geo: Option<&GeoData>) -> Option<bool> {
+    let target_ip = query.target_ip();
+    let mut results = vec![];
+
+    if let Some(domains) = &rule.domain {
+        results.push(match target_ip {
+            Some(_) => Some(false),
+            None => any_of(domains, |d| match_domain(&query.target, d, geo)),
+        });
+    }
+    if let Some(ips) = &rule.ip {
+        results.push(match target_ip {
+            Some(ip) => any_of(ips, |cidr| match_cidr(ip, cidr, geo)),
+            None => Some(false),
+        });
+    }
+    if let Some(sources) = &rule.source {
+        results.push(match query.source {
+            Some(ip) => any_of(sources, |cidr| match_cidr(ip, cidr, geo)),
+            None => Some(false),
+        });
+    }
+    if let Some(port) = &rule.port {
+        results.push(Some(query.port.is_some_and(|p| match_port(p, port))));
+    }
+    if let Some(network) = &rule.network {
+        let matched = network.split(',').any(|n| n.trim() == query.network());
+        results.push(Some(matched));
+    }
+    let contains = |values: &[Cow<'static, str>], value: &Option<String>| {
+        value
+            .as_deref()
+            .is_some_and(|value| values.iter().any(|v| v == value))
+    };
+    if let Some(tags) = &rule.inbound_tag {
+        results.push(Some(contains(tags, &query.inbound_tag)));
+    }
+    if let Some(protocols) = &rule.protocol {
+        results.push(Some(contains(protocols, &query.protocol)));
+    }
+    if let Some(users) = &rule.user {
+        results.push(Some(contains(users, &query.user)));
+    }
+    if rule.attrs.is_some() {
+        // a starlark expression over http headers
+        results.push(None);
+    }
+
+    if results.contains(&Some(false)) {
+        Some(false)
+    } else if results.contains(&None) {
+        None
+    } else {
+        Some(true)
+    }
+}
+
+impl CoreConfig {
+    /// Find the first routing rule matching `query` without the core
+    pub fn match_route(&self, query: &RouteQuery, geo: Option<&GeoData>) -> RouteMatch {
+        let mut unresolved = vec![];
+        for (index, rule) in self.routing.rules.iter().enumerate() {
+            match match_rule(rule, query, geo) {
+                Some(true) => {
+                    return RouteMatch {
+                        rule_index: Some(index),
+                        rule: Some(rule.clone()),
+                        outbound_tag: rule
+                            .balancer_tag
+                            .is_none()
+                            .then(|| rule.outbound_tag.to_string()),
+                        balancer_tag: rule.balancer_tag.as_ref().map(|tag| tag.to_string()),
+                        unresolved,
+                        core_outbound_tag: None,
+                    }
+                }
+                Some(false) => {}
+                None => unresolved.push(index),
+            }
+        }
+        // the core sends unmatched traffic to the first outbound
+        RouteMatch {
+            outbound_tag: self.outbounds.first().map(|o| o.tag.to_string()),
+            unresolved,
+            ..RouteMatch::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::raw::default_core;
+
+    fn domain_rule(domain: &str) -> Rule {
+        Rule {
+            domain: Some(vec![domain.to_string().into()]),
+            ..Rule::new("proxy".into())
+        }
+    }
+
+    fn query(target: &str) -> RouteQuery {
+        RouteQuery {
+            target: target.into(),
+            port: Some(443),
+            ..RouteQuery::default()
+        }
+    }
+
+    #[test]
+    fn test_match_domain() {
+        let q = query("www.Example.com");
+        assert_eq!(
+            match_rule(&domain_rule("domain:example.com"), &q, None),
+            Some(true)
+        );
+        assert_eq!(
+            match_rule(&domain_rule("domain:ample.com"), &q, None),
+            Some(false)
+        );
+        assert_eq!(
+            match_rule(&domain_rule("full:example.com"), &q, None),
+            Some(false)
+        );
+        assert_eq!(
+            match_rule(&domain_rule("keyword:exam"), &q, None),
+            Some(true)
+        );
+        assert_eq!(match_rule(&domain_rule("example"), &q, None), Some(true));
+        assert_eq!(
+            match_rule(&domain_rule(r"regexp:\.com$"), &q, None),
+            Some(true)
+        );
+        // `\D` would turn into `\d` if the pattern were lowercased
+        assert_eq!(
+            match_rule(&domain_rule(r"regexp:^\D+\.com$"), &q, None),
+            Some(true)
+        );
+        assert_eq!(match_rule(&domain_rule("geosite:cn"), &q, None), None);
+        // a domain rule never matches an ip
+        assert_eq!(
+            match_rule(&domain_rule("example"), &query("1.1.1.1"), None),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_match_ip_port_network() {
+        let rule = Rule {
+            ip: Some(vec!["10.0.0.0/8".into(), "fc00::/7".into()]),
+            port: Some("80,400-500".into()),
+            network: Some("tcp".into()),
+            ..Rule::new("direct".into())
+        };
+        assert_eq!(match_rule(&rule, &query("10.1.2.3"), None), Some(true));
+        assert_eq!(match_rule(&rule, &query("fd00::1"), None), Some(true));
+        assert_eq!(match_rule(&rule, &query("11.0.0.1"), None), Some(false));
+        let udp = RouteQuery {
+            network: Some("udp".into()),
+            ..query("10.1.2.3")
+        };
+        assert_eq!(match_rule(&rule, &udp, None), Some(false));
+        let port = RouteQuery {
+            port: Some(8080),
+            ..query("10.1.2.3")
+        };
+        assert_eq!(match_rule(&rule, &port, None), Some(false));
+    }
+
+    #[test]
+    fn test_match_route() {
+        let mut core = default_core();
+        core.routing.rules.push(domain_rule("domain:example.com"));
+
+        let matched = core.match_route(&query("example.com"), None);
+        let index = core.routing.rules.len() - 1;
+        assert_eq!(matched.rule_index, Some(index));
+        assert_eq!(matched.outbound_tag.as_deref(), Some("proxy"));
+        // `geosite:category-ads` cannot be checked offline
+        assert!(!matched.unresolved.is_empty());
+
+        let api = RouteQuery {
+            inbound_tag: Some("api".into()),
+            ..query("127.0.0.1")
+        };
+        assert_eq!(core.match_route(&api, None).rule_index, Some(0));
+
+        let unmatched = core.match_route(&query("example.org"), None);
+        assert_eq!(unmatched.rule_index, None);
+        assert_eq!(unmatched.outbound_tag.as_deref(), Some("direct"));
+    }
+}
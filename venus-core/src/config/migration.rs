@@ -0,0 +1,243 @@
+use toml::{Table, Value};
+
+use crate::config::error::{ConfigError, ConfigResult};
+
+/// Schema version of `config.toml` written by this build
+///
+/// Bump it together with a new step in `MIGRATIONS` whenever the
+/// layout of `VenusConfig` changes in a way old files cannot be read.
+pub const CONFIG_SCHEMA_VERSION: u32 = 4;
+
+/// Key of the schema version in `config.toml`, missing in files before schema 1
+const SCHEMA_KEY: &str = "schemaVersion";
+
+type Migration = fn(&mut Table);
+
+/// `MIGRATIONS[n]` upgrades a config of schema `n` to `n + 1`
+const MIGRATIONS: [Migration; CONFIG_SCHEMA_VERSION as usize] =
+    [v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4];
+
+/// Schema version of a raw config
+pub fn schema_version(config: &Table) -> u32 {
+    config
+        .get(SCHEMA_KEY)
+        .and_then(Value::as_integer)
+        .and_then(|version| u32::try_from(version).ok())
+        .unwrap_or_default()
+}
+
+/// Upgrade a raw config to `CONFIG_SCHEMA_VERSION`
+///
+/// # Returns
+/// * `true` if any migration ran
+pub fn migrate(config: &mut Table) -> ConfigResult<bool> {
+    let version = schema_version(config);
+    if version > CONFIG_SCHEMA_VERSION {
+        return Err(ConfigError::UnsupportedSchema(version));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(config);
+    }
+    config.insert(
+        SCHEMA_KEY.into(),
+        Value::Integer(CONFIG_SCHEMA_VERSION.into()),
+    );
+    Ok(version < CONFIG_SCHEMA_VERSION)
+}
+
+fn get_str<'a>(table: &'a Table, key: &str) -> &'a str {
+    table.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+/// Schema 0 to 1: subscriptions without `updated` and nodes without `nodeId`
+///
+/// Nodes saved before ids existed cannot be selected, their id is
+/// generated the same way as `parse_node` does.
+fn v0_to_v1(config: &mut Table) {
+    let Some(subscriptions) = config
+        .get_mut("subscriptions")
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    for subscription in subscriptions.iter_mut().filter_map(Value::as_table_mut) {
+        subscription
+            .entry("updated")
+            .or_insert_with(|| Value::String("1970-01-01T00:00:00Z".into()));
+        let name = get_str(subscription, "name").to_string();
+
+        let Some(nodes) = subscription.get_mut("nodes").and_then(Value::as_array_mut) else {
+            continue;
+        };
+        for (index, node) in nodes.iter_mut().filter_map(Value::as_table_mut).enumerate() {
+            if !node.contains_key("nodeId") {
+                let id_data = format!(
+                    "{}-{}-{}-{}",
+                    get_str(node, "ps"),
+                    get_str(node, "add"),
+                    get_str(node, "port"),
+                    index
+                );
+                let node_id = format!("{:x}", md5::compute(id_data));
+                node.insert("nodeId".into(), Value::String(node_id));
+            }
+            node.entry("subs")
+                .or_insert_with(|| Value::String(name.clone()));
+        }
+    }
+}
+
+/// Schema 1 to 2: fill `subscriptions` and `settings` fields that became required
+fn v1_to_v2(config: &mut Table) {
+    config
+        .entry("subscriptions")
+        .or_insert_with(|| Value::Array(vec![]));
+    let settings = config
+        .entry("settings")
+        .or_insert_with(|| Value::Table(Table::new()));
+    let Some(settings) = settings.as_table_mut() else {
+        return;
+    };
+    settings
+        .entry("speedUrl")
+        .or_insert_with(|| Value::String(String::new()));
+    settings
+        .entry("currentId")
+        .or_insert_with(|| Value::String(String::new()));
+    settings.entry("logging").or_insert(Value::Boolean(false));
+}
+
+/// Schema 2 to 3: `settings.routingMode`, existing rules are kept as they are
+fn v2_to_v3(config: &mut Table) {
+    let Some(settings) = config.get_mut("settings").and_then(Value::as_table_mut) else {
+        return;
+    };
+    settings
+        .entry("routingMode")
+        .or_insert_with(|| Value::String("rule".into()));
+}
+
+/// Schema 3 to 4: `settings.lanAllowlist`
+fn v3_to_v4(config: &mut Table) {
+    let Some(settings) = config.get_mut("settings").and_then(Value::as_table_mut) else {
+        return;
+    };
+    settings
+        .entry("lanAllowlist")
+        .or_insert_with(|| Value::Array(vec![]));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{routing::RoutingMode, types::VenusConfig};
+
+    const V0_CONFIG: &str = r#"
+version = "0.1.0"
+
+[settings]
+speedUrl = ""
+currentId = ""
+logging = false
+
+[[subscriptions]]
+name = "sub"
+url = "https://example.com/sub"
+
+[[subscriptions.nodes]]
+v = "2"
+ps = "node"
+add = "example.com"
+port = "443"
+id = "66ad4540-b58c-4ad2-9926-ea63445a9b57"
+aid = "0"
+net = "ws"
+type = "none"
+host = ""
+path = "/ray"
+tls = "tls"
+sni = ""
+alpn = ""
+"#;
+
+    #[test]
+    fn test_migrate_v0_to_v1() {
+        let mut config = toml::from_str::<Table>(V0_CONFIG).unwrap();
+        v0_to_v1(&mut config);
+
+        let subscription = config["subscriptions"][0].as_table().unwrap();
+        assert_eq!(
+            subscription["updated"].as_str(),
+            Some("1970-01-01T00:00:00Z")
+        );
+        let node = subscription["nodes"][0].as_table().unwrap();
+        let expected = format!("{:x}", md5::compute("node-example.com-443-0"));
+        assert_eq!(node["nodeId"].as_str(), Some(expected.as_str()));
+        assert_eq!(node["subs"].as_str(), Some("sub"));
+    }
+
+    #[test]
+    fn test_migrate_v1_to_v2() {
+        let mut config = toml::from_str::<Table>("version = \"0.1.0\"\nschemaVersion = 1").unwrap();
+        v1_to_v2(&mut config);
+
+        assert_eq!(config["subscriptions"].as_array().map(Vec::len), Some(0));
+        assert_eq!(config["settings"]["currentId"].as_str(), Some(""));
+        assert_eq!(config["settings"]["logging"].as_bool(), Some(false));
+    }
+
+    #[test]
+    fn test_migrate_v2_to_v3() {
+        let mut config = toml::from_str::<Table>("schemaVersion = 2\n[settings]").unwrap();
+        v2_to_v3(&mut config);
+        assert_eq!(config["settings"]["routingMode"].as_str(), Some("rule"));
+
+        // an existing mode is kept
+        config["settings"]["routingMode"] = Value::String("global".into());
+        v2_to_v3(&mut config);
+        assert_eq!(config["settings"]["routingMode"].as_str(), Some("global"));
+    }
+
+    #[test]
+    fn test_migrate_v3_to_v4() {
+        let mut config = toml::from_str::<Table>("schemaVersion = 3\n[settings]").unwrap();
+        v3_to_v4(&mut config);
+        assert_eq!(
+            config["settings"]["lanAllowlist"].as_array().map(Vec::len),
+            Some(0)
+        );
+
+        // an existing allowlist is kept
+        config["settings"]["lanAllowlist"] = Value::Array(vec![Value::String("10.0.0.0/8".into())]);
+        v3_to_v4(&mut config);
+        assert_eq!(
+            config["settings"]["lanAllowlist"].as_array().map(Vec::len),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_migrate_chain() {
+        let mut config = toml::from_str::<Table>(V0_CONFIG).unwrap();
+        assert!(migrate(&mut config).unwrap());
+        assert_eq!(schema_version(&config), CONFIG_SCHEMA_VERSION);
+        let venus = Value::Table(config.clone())
+            .try_into::<VenusConfig>()
+            .unwrap();
+        assert!(venus.subscriptions[0].nodes[0].node_id.is_some());
+        assert_eq!(venus.settings.routing_mode, RoutingMode::Rule);
+        assert!(venus.settings.lan_allowlist.is_empty());
+
+        // already current
+        assert!(!migrate(&mut config).unwrap());
+
+        config.insert(
+            SCHEMA_KEY.into(),
+            Value::Integer((CONFIG_SCHEMA_VERSION + 1).into()),
+        );
+        assert!(matches!(
+            migrate(&mut config),
+            Err(ConfigError::UnsupportedSchema(_))
+        ));
+    }
+}
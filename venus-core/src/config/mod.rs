@@ -1,19 +1,62 @@
 use anyhow::Context;
+use backup::{
+    backup_before_migration, list_backups, read_backup, write_atomic, write_with_backup, Backup,
+    BackupKind,
+};
 use error::{ConfigError, ConfigResult};
+use history::{
+    diff_snapshots, history_dir, list_snapshots, load_snapshot, record_snapshot, DiffEntry,
+    Snapshot, SnapshotInfo,
+};
 use json_comments::StripComments;
 use log::{debug, info};
+use migration::{migrate, schema_version, CONFIG_SCHEMA_VERSION};
+use raw::parse_core_raw;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::{File, OpenOptions},
-    io::{Read, Write},
+    cmp::Reverse,
+    fs::{self, File},
+    io::{self, Read},
     path::PathBuf,
 };
 use types::{CoreConfig, VenusConfig};
+use watcher::{ConfigSync, FileSyncStatus, SyncStatus};
 
-use crate::consts::{VENUS_CONFIG_PATH, VENUS_V2RAY_PATH, VERSION};
+use crate::consts::{
+    VENUS_CONFIG_BACKUPS, VENUS_CONFIG_HISTORY, VENUS_CONFIG_PATH, VENUS_V2RAY_PATH, VERSION,
+};
+use crate::error::log_err;
 
+pub mod backup;
+pub mod cidr;
+pub mod dns;
 pub mod error;
+pub mod history;
+pub mod inbound;
+pub mod matcher;
+pub mod migration;
+pub mod policy;
+pub mod raw;
+pub mod routing;
+pub mod tproxy;
+pub mod tun;
 pub mod types;
+pub mod watcher;
+
+/// Path of the v2ray core config file
+pub fn core_config_path() -> PathBuf {
+    PathBuf::from(format!("{}/config.json", *VENUS_V2RAY_PATH))
+}
+
+/// Path of the venus config file
+pub fn venus_config_path() -> PathBuf {
+    PathBuf::from(VENUS_CONFIG_PATH.as_ref())
+}
+
+/// Path of the last core config that passed validation and started
+pub fn last_good_core_path() -> PathBuf {
+    PathBuf::from(format!("{}/config.json.last-good", *VENUS_V2RAY_PATH))
+}
 
 /// All config field
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,6 +66,9 @@ pub struct Config {
     pub core: Option<CoreConfig>,
     /// Venus config from `config.toml`
     pub venus: VenusConfig,
+    /// What was last read from or written to both files
+    #[serde(skip)]
+    pub sync: ConfigSync,
 }
 
 /// Core config and global states
@@ -33,25 +79,49 @@ impl Config {
         let config = Self {
             core: None,
             venus: v_config,
+            sync: ConfigSync::default(),
         };
 
         Ok(config)
     }
 
+    /// Loaded core config
+    ///
+    /// # Returns
+    /// * `ConfigError::Empty` before `config.json` has been read
+    pub fn core_config(&self) -> ConfigResult<&CoreConfig> {
+        self.core
+            .as_ref()
+            .ok_or(ConfigError::Empty("v2ray core config is empty".into()))
+    }
+
     pub fn reload_rua(&mut self) -> ConfigResult<()> {
-        let path = PathBuf::from(VENUS_CONFIG_PATH.as_ref());
+        let path = venus_config_path();
         if !path.exists() {
             info!("venus config file not exist, creating default");
             self.write_rua()?;
             return Ok(());
         }
-        let mut config_file = File::open(path)?;
+        let mut config_file = File::open(&path)?;
         let mut buffer = String::new();
         config_file.read_to_string(&mut buffer)?;
-        let mut rua_config = toml::from_str::<VenusConfig>(&buffer)?;
+        let mut raw_config = toml::from_str::<toml::Table>(&buffer)?;
+        let from = schema_version(&raw_config);
+        let migrated = migrate(&mut raw_config)?;
+        if migrated {
+            let backup = backup_before_migration(&path, buffer.as_bytes(), from)?;
+            info!("venus config migrated from schema {from} to {CONFIG_SCHEMA_VERSION}, previous config kept as {backup:?}");
+        }
+        let mut rua_config = toml::Value::Table(raw_config).try_into::<VenusConfig>()?;
         // debug!("reloading rua config: {:?}", rua_config);
         rua_config.version = VERSION.into();
         self.venus = rua_config;
+        self.sync
+            .venus
+            .synced(buffer.into_bytes(), toml::to_string(&self.venus)?);
+        if migrated {
+            self.write_rua()?;
+        }
         Ok(())
     }
 
@@ -62,50 +132,260 @@ impl Config {
         let ctx = || format!("reload config {} failed", path_ctx);
 
         let path = PathBuf::from(path_str);
-        let core_file = File::open(path).with_context(ctx)?;
-        let stripped = StripComments::new(core_file);
+        let content = fs::read(path).with_context(ctx)?;
+        let stripped = StripComments::new(content.as_slice());
         let core_config: CoreConfig = serde_json::from_reader(stripped).with_context(ctx)?;
         debug!("reloading core config: {:?}", core_config);
+        let memory = serde_json::to_string_pretty(&core_config)?;
         self.core = Some(core_config);
+        self.sync.core.synced(content, memory);
         Ok(())
     }
 
     ///  Write core config to config file
+    ///
+    /// The file is replaced atomically, the previous content is kept as a backup.
+    /// Refused with `ConfigError::Conflict` when the file was edited outside venus
+    /// and not reloaded yet.
     pub fn write_core(&mut self) -> ConfigResult<()> {
-        let path = format!("{}/config.json", &*VENUS_V2RAY_PATH);
-        let path_ctx = path.clone();
-        let ctx = || format!("write config {} failed", path_ctx);
+        let path = core_config_path();
+        let ctx = || format!("write config {:?} failed", core_config_path());
 
-        let path = PathBuf::from(path);
         let config = self.core.as_ref().ok_or(ConfigError::Empty(
             "write_core: v2ray core config is empty".into(),
         ))?;
-        let core_file = OpenOptions::new()
-            .write(true)
-            .open(path)
+        let core_string = serde_json::to_string_pretty(&config)?;
+        self.check_synced(BackupKind::Core)?;
+        write_with_backup(&path, core_string.as_bytes(), *VENUS_CONFIG_BACKUPS)
             .with_context(ctx)?;
-        core_file.set_len(0).with_context(ctx)?;
-        serde_json::to_writer_pretty(&core_file, &config)?;
+        self.sync
+            .core
+            .synced(core_string.clone().into_bytes(), core_string);
         Ok(())
     }
 
+    /// Exact text of `config.json`, comments included
+    pub fn read_core_raw(&self) -> ConfigResult<String> {
+        Ok(fs::read_to_string(core_config_path())?)
+    }
+
+    /// Write `config.json` as is and reload the in-memory core config from it
+    ///
+    /// The text is parsed first, it is not validated by the core here.
+    /// Refused with `ConfigError::Conflict` like `write_core`.
+    pub fn write_core_raw(&mut self, raw: &str) -> ConfigResult<()> {
+        let path = core_config_path();
+        let core_config = parse_core_raw(raw)?;
+        self.check_synced(BackupKind::Core)?;
+        write_with_backup(&path, raw.as_bytes(), *VENUS_CONFIG_BACKUPS)?;
+        let memory = serde_json::to_string_pretty(&core_config)?;
+        self.core = Some(core_config);
+        self.sync.core.synced(raw.as_bytes().to_vec(), memory);
+        Ok(())
+    }
+
+    /// Keep a copy of the current `config.json` as the last-known-good one
+    pub fn save_last_good_core(&self) -> ConfigResult<()> {
+        let ctx = || "save last-known-good core config failed";
+        let content = fs::read(core_config_path()).with_context(ctx)?;
+        write_atomic(&last_good_core_path(), &content).with_context(ctx)?;
+        Ok(())
+    }
+
+    /// Restore `config.json` from the last-known-good copy and reload it
+    ///
+    /// Returns `false` when there is no last-known-good copy yet.
+    pub fn restore_last_good_core(&mut self) -> ConfigResult<bool> {
+        let last_good = last_good_core_path();
+        if !last_good.exists() {
+            return Ok(false);
+        }
+        let ctx = || "restore last-known-good core config failed";
+        let content = fs::read(last_good).with_context(ctx)?;
+        write_atomic(&core_config_path(), &content).with_context(ctx)?;
+        self.reload_core()?;
+        info!("core config restored from last-known-good copy");
+        Ok(true)
+    }
+
+    /// Write venus config to config file
+    ///
+    /// The file is replaced atomically, the previous content is kept as a backup.
+    /// Refused with `ConfigError::Conflict` when the file was edited outside venus
+    /// and not reloaded yet.
     pub fn write_rua(&mut self) -> ConfigResult<()> {
-        let path = PathBuf::from(VENUS_CONFIG_PATH.as_ref());
+        let path = venus_config_path();
         let path_ctx = path.clone();
         let ctx = || format!("write config {:?} failed", path_ctx);
 
-        let mut rua_file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)?;
         let rua_string = toml::to_string(&self.venus).with_context(ctx)?;
-        rua_file.set_len(0).with_context(ctx)?;
-        rua_file
-            .write_all(rua_string.as_bytes())
-            .with_context(ctx)?;
+        self.check_synced(BackupKind::Venus)?;
+        write_with_backup(&path, rua_string.as_bytes(), *VENUS_CONFIG_BACKUPS).with_context(ctx)?;
+        self.sync
+            .venus
+            .synced(rua_string.clone().into_bytes(), rua_string);
         Ok(())
     }
+
+    /// Compare a config file with what venus last read or wrote
+    pub fn sync_status(&self, kind: BackupKind) -> ConfigResult<SyncStatus> {
+        let (path, memory) = match kind {
+            BackupKind::Venus => (venus_config_path(), toml::to_string(&self.venus)?),
+            BackupKind::Core => (
+                core_config_path(),
+                serde_json::to_string_pretty(&self.core)?,
+            ),
+        };
+        let disk = match fs::read(path) {
+            Ok(disk) => disk,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(SyncStatus::Synced),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(self.sync.get(kind).status(&disk, &memory))
+    }
+
+    /// Refuse with `ConfigError::Conflict` when a config file was edited
+    /// outside venus and not reloaded yet, as writing it would
+    pub fn check_synced(&self, kind: BackupKind) -> ConfigResult<()> {
+        if self.sync_status(kind)? != SyncStatus::Synced {
+            let path = match kind {
+                BackupKind::Venus => venus_config_path(),
+                BackupKind::Core => core_config_path(),
+            };
+            return Err(ConfigError::Conflict(path));
+        }
+        Ok(())
+    }
+
+    /// Sync status of both config files
+    pub fn sync_statuses(&self) -> ConfigResult<Vec<FileSyncStatus>> {
+        [BackupKind::Venus, BackupKind::Core]
+            .into_iter()
+            .map(|kind| {
+                Ok(FileSyncStatus {
+                    kind,
+                    status: self.sync_status(kind)?,
+                    error: self.sync.get(kind).error.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Overwrite a config file with the in-memory config, discarding edits made outside venus
+    pub fn overwrite(&mut self, kind: BackupKind) -> ConfigResult<()> {
+        self.sync.get_mut(kind).forget_disk();
+        match kind {
+            BackupKind::Venus => self.write_rua(),
+            BackupKind::Core => self.write_core(),
+        }
+    }
+
+    /// Backups of both config files, newest first
+    pub fn list_backups(&self) -> ConfigResult<Vec<Backup>> {
+        let mut backups = list_backups(&venus_config_path(), BackupKind::Venus)?;
+        backups.extend(list_backups(&core_config_path(), BackupKind::Core)?);
+        backups.sort_by_key(|backup| Reverse(backup.created_at));
+        Ok(backups)
+    }
+
+    /// Kind and content of a backup by its file name
+    pub fn read_backup(&self, name: &str) -> ConfigResult<(BackupKind, Vec<u8>)> {
+        let backup = self
+            .list_backups()?
+            .into_iter()
+            .find(|backup| backup.name == name)
+            .ok_or(ConfigError::BackupNotFound(name.to_string()))?;
+        let path = match backup.kind {
+            BackupKind::Venus => venus_config_path(),
+            BackupKind::Core => core_config_path(),
+        };
+        Ok((backup.kind, read_backup(&path, name)?))
+    }
+
+    /// Write a venus config backup over `config.toml` and reload it
+    ///
+    /// The backup is parsed and migrated first, nothing changes when it is invalid.
+    /// Refused with `ConfigError::Conflict` like `write_rua`.
+    pub fn restore_venus_backup(&mut self, content: &[u8]) -> ConfigResult<()> {
+        let path = venus_config_path();
+        let text = std::str::from_utf8(content).context("venus config backup is not UTF-8")?;
+        let mut raw_config = toml::from_str::<toml::Table>(text)?;
+        migrate(&mut raw_config)?;
+        toml::Value::Table(raw_config).try_into::<VenusConfig>()?;
+        self.check_synced(BackupKind::Venus)?;
+        write_with_backup(&path, content, *VENUS_CONFIG_BACKUPS)?;
+        self.reload_rua()
+    }
+
+    /// Record the current configs in the history if they changed
+    ///
+    /// # Parameters
+    /// * `author`: JWT `sub` of who made the change
+    /// * `reason`: short description of the change
+    pub fn record_history(&self, author: &str, reason: &str) -> ConfigResult<Option<SnapshotInfo>> {
+        record_snapshot(
+            &history_dir(&venus_config_path()),
+            &self.venus,
+            self.core.as_ref(),
+            author,
+            reason,
+            *VENUS_CONFIG_HISTORY,
+        )
+    }
+
+    /// Recorded config changes, newest first
+    pub fn history(&self) -> ConfigResult<Vec<SnapshotInfo>> {
+        list_snapshots(&history_dir(&venus_config_path()))
+    }
+
+    /// Structural diff between two history snapshots
+    pub fn history_diff(&self, from: u64, to: u64) -> ConfigResult<Vec<DiffEntry>> {
+        let dir = history_dir(&venus_config_path());
+        diff_snapshots(&load_snapshot(&dir, from)?, &load_snapshot(&dir, to)?)
+    }
+
+    /// A recorded snapshot by its id
+    pub fn snapshot(&self, id: u64) -> ConfigResult<Snapshot> {
+        load_snapshot(&history_dir(&venus_config_path()), id)
+    }
+
+    /// Replace the configs with a history snapshot and write them
+    ///
+    /// The current user is kept. The core is not restarted here.
+    /// When a write fails the previous configs are put back.
+    pub fn revert(&mut self, snapshot: &Snapshot) -> ConfigResult<()> {
+        let previous = (self.venus.clone(), self.core.clone());
+        let user = self.venus.user.clone();
+        self.venus = VenusConfig {
+            version: VERSION.into(),
+            schema_version: CONFIG_SCHEMA_VERSION,
+            user,
+            ..snapshot.venus.clone()
+        };
+        if let Err(err) = self.write_rua() {
+            (self.venus, self.core) = previous;
+            return Err(err);
+        }
+        if snapshot.core.is_some() {
+            self.core = snapshot.core.clone();
+            if let Err(err) = self.write_core() {
+                self.undo_revert(previous);
+                return Err(err);
+            }
+        }
+        info!("config reverted to snapshot {}", snapshot.id);
+        Ok(())
+    }
+
+    /// Put back and write the configs `revert` replaced
+    pub fn undo_revert(&mut self, (venus, core): (VenusConfig, Option<CoreConfig>)) {
+        let core_changed = self.core != core;
+        (self.venus, self.core) = (venus, core);
+        self.write_rua().map_err(log_err).ok();
+        if core_changed {
+            self.write_core().map_err(log_err).ok();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,82 @@
+use crate::config::{
+    error::{ConfigError, ConfigResult},
+    types::{CoreConfig, Policy},
+};
+
+/// Longest timeout accepted, in seconds
+const MAX_TIMEOUT: i64 = 24 * 60 * 60;
+/// Largest connection buffer accepted, in KB
+const MAX_BUFFER_SIZE: i64 = 64 * 1024;
+
+fn invalid(message: impl Into<String>) -> ConfigError {
+    ConfigError::InvalidPolicy(message.into())
+}
+
+impl CoreConfig {
+    /// Replace the policy after checking its levels
+    ///
+    /// Level keys are user levels, timeouts are seconds and buffer sizes KB.
+    /// Unset values keep the core defaults.
+    pub fn set_policy(&mut self, policy: Policy) -> ConfigResult<()> {
+        for (level, limits) in &policy.levels {
+            level
+                .parse::<u32>()
+                .map_err(|_| invalid(format!("level {level} is not a number")))?;
+            let timeouts = [
+                ("handshake", limits.handshake, 1),
+                ("connIdle", limits.conn_idle, 1),
+                ("uplinkOnly", limits.uplink_only, 0),
+                ("downlinkOnly", limits.downlink_only, 0),
+            ];
+            for (name, value, min) in timeouts {
+                if value.is_some_and(|value| !(min..=MAX_TIMEOUT).contains(&value)) {
+                    return Err(invalid(format!(
+                        "level {level} {name} should be {min} to {MAX_TIMEOUT} seconds"
+                    )));
+                }
+            }
+            if limits
+                .buffer_size
+                .is_some_and(|size| !(0..=MAX_BUFFER_SIZE).contains(&size))
+            {
+                return Err(invalid(format!(
+                    "level {level} bufferSize should be 0 to {MAX_BUFFER_SIZE} KB"
+                )));
+            }
+        }
+        self.policy = policy;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{raw::default_core, types::Levels};
+
+    #[test]
+    fn test_set_policy() {
+        let mut core = default_core();
+        let mut policy = core.policy.clone();
+        policy.levels.insert(
+            "1".into(),
+            Levels {
+                handshake: Some(4),
+                conn_idle: Some(60),
+                buffer_size: Some(0),
+                ..Levels::default()
+            },
+        );
+        core.set_policy(policy.clone()).unwrap();
+        let json = serde_json::to_value(&core).unwrap();
+        assert_eq!(json["policy"]["levels"]["1"]["connIdle"], 60);
+        assert_eq!(json["policy"]["levels"]["1"]["bufferSize"], 0);
+
+        let mut invalid = policy.clone();
+        invalid.levels.get_mut("1").unwrap().conn_idle = Some(0);
+        assert!(core.set_policy(invalid).is_err());
+        let mut invalid = policy;
+        invalid.levels.insert("mobile".into(), Levels::default());
+        assert!(core.set_policy(invalid).is_err());
+        assert_eq!(core.policy.levels["1"].conn_idle, Some(60));
+    }
+}
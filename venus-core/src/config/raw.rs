@@ -0,0 +1,95 @@
+use json_comments::StripComments;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    error::{ConfigError, ConfigResult},
+    types::CoreConfig,
+};
+
+/// Which check found a problem in a raw core config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IssueSource {
+    /// Not valid JSON or not a `CoreConfig`
+    Parse,
+    /// Rejected by the core's test mode
+    Core,
+}
+
+/// A problem in a raw core config, for editor markers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigIssue {
+    pub source: IssueSource,
+    /// 1-based, `None` when the core does not tell
+    pub line: Option<usize>,
+    /// 1-based, `None` when the core does not tell
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl From<&ConfigError> for ConfigIssue {
+    fn from(err: &ConfigError) -> Self {
+        match err {
+            ConfigError::InvalidCore {
+                line,
+                column,
+                message,
+            } => Self {
+                source: IssueSource::Parse,
+                line: Some(*line),
+                column: Some(*column),
+                message: message.clone(),
+            },
+            err => Self {
+                source: IssueSource::Parse,
+                line: None,
+                column: None,
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+/// Parse `config.json` text, comments allowed
+///
+/// Comments are blanked out rather than removed,
+/// so error positions match the original text.
+pub fn parse_core_raw(raw: &str) -> ConfigResult<CoreConfig> {
+    let stripped = StripComments::new(raw.as_bytes());
+    serde_json::from_reader(stripped).map_err(|err| ConfigError::InvalidCore {
+        line: err.line(),
+        column: err.column(),
+        message: err.to_string(),
+    })
+}
+
+/// The default `config/config.json` shipped with venus, for tests
+#[cfg(test)]
+pub(crate) fn default_core() -> CoreConfig {
+    parse_core_raw(include_str!("../../../config/config.json")).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_core_raw() {
+        let raw = r#"{
+    // comments are kept by the editor
+    "inbounds": [],
+    "outbounds": [],
+}"#;
+        let err = parse_core_raw(raw).unwrap_err();
+        let issue = ConfigIssue::from(&err);
+        assert_eq!(issue.source, IssueSource::Parse);
+        assert_eq!((issue.line, issue.column), (Some(5), Some(1)));
+
+        let raw = format!(
+            "// default config\n{}",
+            include_str!("../../../config/config.json")
+        );
+        assert!(parse_core_raw(&raw).is_ok());
+    }
+}
@@ -0,0 +1,417 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{
+        cidr::parse_cidr,
+        error::{ConfigError, ConfigResult},
+        types::{CoreConfig, Rule},
+    },
+    consts::{BLOCKED_OUTBOUND_TAG, DIRECT_OUTBOUND_TAG, PRESET_RULE_TAG, PROXY_OUTBOUND_TAG},
+};
+
+/// Domain matcher prefixes understood by the core, `plain` when there is none
+const DOMAIN_PREFIXES: [&str; 6] = ["domain", "full", "regexp", "keyword", "geosite", "ext"];
+/// Sniffed protocols a rule can match
+const RULE_PROTOCOLS: [&str; 3] = ["http", "tls", "bittorrent"];
+const RULE_NETWORKS: [&str; 2] = ["tcp", "udp"];
+
+/// One-click routing, its preset rules follow the custom rules
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RoutingMode {
+    /// Everything through the proxy
+    Global,
+    /// Only the custom rules, unmatched traffic goes to the first outbound
+    #[default]
+    Rule,
+    /// Everything direct
+    Direct,
+    /// LAN addresses direct, everything else through the proxy
+    BypassLan,
+    /// LAN and China direct, ads blocked, everything else through the proxy
+    BypassChina,
+}
+
+impl RoutingMode {
+    /// Rules appended after the custom rules, tagged with [`PRESET_RULE_TAG`]
+    pub fn preset_rules(self) -> Vec<Rule> {
+        let rule = |tag: &'static str| Rule {
+            rule_tag: Some(PRESET_RULE_TAG.into()),
+            ..Rule::new(tag.into())
+        };
+        let catch_all = |tag| Rule {
+            network: Some("tcp,udp".into()),
+            ..rule(tag)
+        };
+        let ip = |ip: &'static str, tag| Rule {
+            ip: Some(vec![ip.into()]),
+            ..rule(tag)
+        };
+        let domain = |domain: &'static str, tag| Rule {
+            domain: Some(vec![domain.into()]),
+            ..rule(tag)
+        };
+        match self {
+            Self::Global => vec![catch_all(PROXY_OUTBOUND_TAG)],
+            Self::Rule => vec![],
+            Self::Direct => vec![catch_all(DIRECT_OUTBOUND_TAG)],
+            Self::BypassLan => vec![
+                ip("geoip:private", DIRECT_OUTBOUND_TAG),
+                catch_all(PROXY_OUTBOUND_TAG),
+            ],
+            Self::BypassChina => vec![
+                domain("geosite:category-ads-all", BLOCKED_OUTBOUND_TAG),
+                ip("geoip:private", DIRECT_OUTBOUND_TAG),
+                domain("geosite:cn", DIRECT_OUTBOUND_TAG),
+                ip("geoip:cn", DIRECT_OUTBOUND_TAG),
+                catch_all(PROXY_OUTBOUND_TAG),
+            ],
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> ConfigError {
+    ConfigError::InvalidRule(message.into())
+}
+
+/// `ext:file.dat:tag`
+fn check_ext(value: &str) -> ConfigResult<()> {
+    match value.split_once(':') {
+        Some((file, tag)) if !file.is_empty() && !tag.is_empty() => Ok(()),
+        _ => Err(invalid(format!("ext:{value} should be ext:<file>:<tag>"))),
+    }
+}
+
+fn check_domain(domain: &str) -> ConfigResult<()> {
+    let Some((prefix, value)) = domain.split_once(':') else {
+        return Ok(());
+    };
+    if !DOMAIN_PREFIXES.contains(&prefix) {
+        // e.g. `example.com:443` is not a domain matcher
+        return Err(invalid(format!("unknown domain prefix in {domain}")));
+    }
+    if value.is_empty() {
+        return Err(invalid(format!("empty {prefix}: domain")));
+    }
+    if prefix == "ext" {
+        check_ext(value)?;
+    }
+    Ok(())
+}
+
+/// IP, CIDR, `geoip:` or `ext:`
+fn check_ip(ip: &str) -> ConfigResult<()> {
+    if let Some(code) = ip.strip_prefix("geoip:") {
+        let code = code.strip_prefix('!').unwrap_or(code);
+        if code.is_empty() {
+            return Err(invalid("empty geoip: code"));
+        }
+        return Ok(());
+    }
+    if let Some(value) = ip.strip_prefix("ext:") {
+        return check_ext(value);
+    }
+    parse_cidr(ip)
+        .map(|_| ())
+        .ok_or_else(|| invalid(format!("invalid ip or cidr {ip}")))
+}
+
+/// `53`, `1000-2000` or a comma separated list of both
+fn check_port(port: &str) -> ConfigResult<()> {
+    let parse = |p: &str| {
+        p.trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|p| *p > 0)
+            .ok_or(invalid(format!("invalid port {port}")))
+    };
+    for part in port.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                if parse(from)? > parse(to)? {
+                    return Err(invalid(format!("invalid port range {part}")));
+                }
+            }
+            None => {
+                parse(part)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_network(network: &str) -> ConfigResult<()> {
+    for part in network.split(',') {
+        if !RULE_NETWORKS.contains(&part.trim()) {
+            return Err(invalid(format!("invalid network {network}")));
+        }
+    }
+    Ok(())
+}
+
+fn is_preset(rule: &Rule) -> bool {
+    rule.rule_tag.as_deref() == Some(PRESET_RULE_TAG)
+}
+
+fn has_values(values: &Option<Vec<Cow<'static, str>>>) -> bool {
+    values.as_ref().is_some_and(|v| !v.is_empty())
+}
+
+impl CoreConfig {
+    /// Check a routing rule against this config
+    ///
+    /// The target outbound, balancer or api tag has to exist, and every condition
+    /// has to use a syntax the core accepts.
+    pub fn validate_rule(&self, rule: &Rule) -> ConfigResult<()> {
+        if rule.type_field != "field" {
+            return Err(invalid(format!("unknown rule type {}", rule.type_field)));
+        }
+
+        match &rule.balancer_tag {
+            Some(tag) => {
+                let exists = self
+                    .routing
+                    .balancers
+                    .iter()
+                    .flatten()
+                    .any(|b| b.tag == *tag);
+                if !exists {
+                    return Err(invalid(format!("balancer {tag} not found")));
+                }
+            }
+            None => {
+                // the api rule targets the api's own tag
+                let is_api = self
+                    .api
+                    .as_ref()
+                    .is_some_and(|api| api.tag == rule.outbound_tag);
+                if !is_api && !self.outbounds.iter().any(|o| o.tag == rule.outbound_tag) {
+                    return Err(invalid(format!("outbound {} not found", rule.outbound_tag)));
+                }
+            }
+        }
+
+        let has_condition = has_values(&rule.domain)
+            || has_values(&rule.ip)
+            || has_values(&rule.source)
+            || has_values(&rule.user)
+            || has_values(&rule.inbound_tag)
+            || has_values(&rule.protocol)
+            || rule.port.is_some()
+            || rule.network.is_some()
+            || rule.attrs.is_some();
+        if !has_condition {
+            return Err(invalid("rule has no condition"));
+        }
+
+        for domain in rule.domain.iter().flatten() {
+            check_domain(domain)?;
+        }
+        for ip in rule.ip.iter().chain(rule.source.iter()).flatten() {
+            check_ip(ip)?;
+        }
+        if let Some(port) = &rule.port {
+            check_port(port)?;
+        }
+        if let Some(network) = &rule.network {
+            check_network(network)?;
+        }
+        for protocol in rule.protocol.iter().flatten() {
+            if !RULE_PROTOCOLS.contains(&protocol.as_ref()) {
+                return Err(invalid(format!("unknown protocol {protocol}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Index of the first preset rule, or the number of rules without one
+    pub fn preset_start(&self) -> usize {
+        let rules = &self.routing.rules;
+        rules.iter().position(is_preset).unwrap_or(rules.len())
+    }
+
+    /// Replace the preset rules with the ones of `mode`
+    ///
+    /// Preset rules are found by their tag wherever they are, custom rules
+    /// are kept in their order in front of the new preset rules.
+    pub fn switch_routing_mode(&mut self, mode: RoutingMode) -> ConfigResult<()> {
+        let preset = mode.preset_rules();
+        for rule in &preset {
+            self.validate_rule(rule)?;
+        }
+        self.routing.rules.retain(|rule| !is_preset(rule));
+        self.routing.rules.extend(preset);
+        Ok(())
+    }
+
+    /// Insert a validated rule at `index`, or append it
+    ///
+    /// # Returns
+    /// * index of the inserted rule
+    pub fn insert_rule(&mut self, index: Option<usize>, rule: Rule) -> ConfigResult<usize> {
+        self.validate_rule(&rule)?;
+        let rules = &mut self.routing.rules;
+        let index = index.unwrap_or(rules.len());
+        if index > rules.len() {
+            return Err(ConfigError::RuleNotFound(index));
+        }
+        rules.insert(index, rule);
+        Ok(index)
+    }
+
+    /// Replace the rule at `index` with a validated one
+    pub fn update_rule(&mut self, index: usize, rule: Rule) -> ConfigResult<()> {
+        self.validate_rule(&rule)?;
+        let current = self
+            .routing
+            .rules
+            .get_mut(index)
+            .ok_or(ConfigError::RuleNotFound(index))?;
+        *current = rule;
+        Ok(())
+    }
+
+    /// Remove the rule at `index`
+    pub fn remove_rule(&mut self, index: usize) -> ConfigResult<Rule> {
+        if index >= self.routing.rules.len() {
+            return Err(ConfigError::RuleNotFound(index));
+        }
+        Ok(self.routing.rules.remove(index))
+    }
+
+    /// Reorder rules, `order[n]` is the current index of the new n-th rule
+    pub fn reorder_rules(&mut self, order: &[usize]) -> ConfigResult<()> {
+        let rules = &mut self.routing.rules;
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        if sorted != (0..rules.len()).collect::<Vec<_>>() {
+            return Err(invalid(format!(
+                "order should contain every index of the {} rules once",
+                rules.len()
+            )));
+        }
+        let reordered = order.iter().map(|i| rules[*i].clone()).collect();
+        *rules = reordered;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{raw::default_core, types::Outbound};
+
+    fn rule(domain: &str) -> Rule {
+        Rule {
+            domain: Some(vec![domain.to_string().into()]),
+            ..Rule::new("direct".into())
+        }
+    }
+
+    #[test]
+    fn test_validate_rule() {
+        let core = default_core();
+        assert!(core.validate_rule(&rule("geosite:cn")).is_ok());
+        for rule in &core.routing.rules {
+            assert!(core.validate_rule(rule).is_ok());
+        }
+        assert!(core.validate_rule(&rule("example.com")).is_ok());
+        assert!(core.validate_rule(&rule("geosite:")).is_err());
+        assert!(core.validate_rule(&rule("site:cn")).is_err());
+        assert!(core.validate_rule(&Rule::new("direct".into())).is_err());
+        assert!(core
+            .validate_rule(&Rule {
+                outbound_tag: "missing".into(),
+                ..rule("geosite:cn")
+            })
+            .is_err());
+
+        let ip_rule = |ip: &str| Rule {
+            ip: Some(vec![ip.to_string().into()]),
+            ..Rule::new("direct".into())
+        };
+        for ip in ["geoip:cn", "geoip:!cn", "10.0.0.0/8", "::1", "fc00::/7"] {
+            assert!(core.validate_rule(&ip_rule(ip)).is_ok(), "{ip}");
+        }
+        for ip in ["geoip:", "10.0.0.0/33", "example.com"] {
+            assert!(core.validate_rule(&ip_rule(ip)).is_err(), "{ip}");
+        }
+
+        let port_rule = |port: &str| Rule {
+            port: Some(port.to_string().into()),
+            ..Rule::new("direct".into())
+        };
+        assert!(core.validate_rule(&port_rule("53,443,1000-2000")).is_ok());
+        assert!(core.validate_rule(&port_rule("2000-1000")).is_err());
+        assert!(core.validate_rule(&port_rule("65536")).is_err());
+    }
+
+    #[test]
+    fn test_switch_routing_mode() {
+        let mut core = default_core();
+        core.outbounds.push(Outbound {
+            tag: PROXY_OUTBOUND_TAG.into(),
+            ..core.outbounds[0].clone()
+        });
+        let custom = core.routing.rules.clone();
+
+        core.switch_routing_mode(RoutingMode::BypassChina).unwrap();
+        assert_eq!(core.preset_start(), custom.len());
+        // past the preset, then the preset is moved around
+        core.insert_rule(None, rule("geosite:google")).unwrap();
+        let mut order = (0..core.routing.rules.len()).collect::<Vec<_>>();
+        order.swap(0, custom.len() + 1);
+        core.reorder_rules(&order).unwrap();
+        let edited = Rule {
+            rule_tag: Some(PRESET_RULE_TAG.into()),
+            ..rule("geosite:apple")
+        };
+        core.update_rule(custom.len() + 2, edited).unwrap();
+
+        core.switch_routing_mode(RoutingMode::Global).unwrap();
+        let rules = &core.routing.rules;
+        assert_eq!(rules.len(), custom.len() + 2);
+        assert!(rules.contains(&rule("geosite:google")));
+        assert_eq!(
+            rules[..custom.len() + 1]
+                .iter()
+                .filter(|r| is_preset(r))
+                .count(),
+            0
+        );
+        assert_eq!(
+            rules[custom.len() + 1..],
+            RoutingMode::Global.preset_rules()[..]
+        );
+
+        core.switch_routing_mode(RoutingMode::Rule).unwrap();
+        assert_eq!(core.routing.rules.len(), custom.len() + 1);
+        assert_eq!(core.preset_start(), custom.len() + 1);
+    }
+
+    #[test]
+    fn test_rule_crud() {
+        let mut core = default_core();
+        let count = core.routing.rules.len();
+        let index = core.insert_rule(Some(0), rule("geosite:cn")).unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(core.routing.rules.len(), count + 1);
+
+        core.update_rule(0, rule("full:example.com")).unwrap();
+        assert!(matches!(
+            core.update_rule(count + 1, rule("geosite:cn")),
+            Err(ConfigError::RuleNotFound(_))
+        ));
+
+        let mut order = (0..=count).rev().collect::<Vec<_>>();
+        core.reorder_rules(&order).unwrap();
+        assert_eq!(core.routing.rules[count], rule("full:example.com"));
+        order.pop();
+        assert!(core.reorder_rules(&order).is_err());
+
+        assert_eq!(core.remove_rule(count).unwrap(), rule("full:example.com"));
+        assert_eq!(core.routing.rules.len(), count);
+    }
+}
@@ -0,0 +1,396 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{
+        error::{ConfigError, ConfigResult},
+        types::{CoreConfig, Inbound, InboundSettings, Sniffing, Sockopt, StreamSettings},
+    },
+    consts::{DEFAULT_OUTBOUND_MARK, TPROXY_INBOUND_TAG},
+};
+
+/// Default port of the dokodemo-door inbound
+const DEFAULT_TPROXY_PORT: u16 = 12345;
+/// Firewall mark routing captured packets to the local table
+const ROUTE_MARK: u32 = 1;
+/// Policy routing table delivering marked packets locally
+const ROUTE_TABLE: u32 = 100;
+
+/// Destinations never captured
+pub(crate) const BYPASS_RANGES: [&str; 10] = [
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+    "255.255.255.255/32",
+];
+
+/// How captured connections reach the core
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TproxyMode {
+    /// `TPROXY` target, tcp and udp
+    #[default]
+    Tproxy,
+    /// `REDIRECT` nat target, tcp only
+    Redirect,
+}
+
+impl TproxyMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Tproxy => "tproxy",
+            Self::Redirect => "redirect",
+        }
+    }
+}
+
+/// Firewall the generated rules are written for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FirewallKind {
+    #[default]
+    Nftables,
+    Iptables,
+}
+
+/// Transparent proxy through a dokodemo-door inbound
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TproxySetting {
+    pub enabled: bool,
+    pub mode: TproxyMode,
+    pub port: u16,
+    /// SO_MARK set on every outbound
+    pub mark: u32,
+}
+
+impl Default for TproxySetting {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: TproxyMode::default(),
+            port: DEFAULT_TPROXY_PORT,
+            mark: DEFAULT_OUTBOUND_MARK,
+        }
+    }
+}
+
+impl CoreConfig {
+    pub(crate) fn tproxy_inbound(&self) -> Option<&Inbound> {
+        self.inbounds
+            .iter()
+            .find(|inbound| inbound.tag == TPROXY_INBOUND_TAG)
+    }
+
+    /// Current transparent proxy setting, read back from the inbound and outbounds
+    pub fn tproxy(&self) -> TproxySetting {
+        let Some(inbound) = self.tproxy_inbound() else {
+            return TproxySetting::default();
+        };
+        let mode = inbound
+            .stream_settings
+            .as_ref()
+            .and_then(|stream| stream.sockopt.as_ref())
+            .and_then(|sockopt| sockopt.tproxy.as_deref());
+        TproxySetting {
+            enabled: true,
+            mode: match mode {
+                Some("redirect") => TproxyMode::Redirect,
+                _ => TproxyMode::Tproxy,
+            },
+            port: inbound.port,
+            mark: self.outbound_mark().unwrap_or(DEFAULT_OUTBOUND_MARK),
+        }
+    }
+
+    /// Mark outbounds need while the transparent proxy or TUN is on
+    pub fn outbound_mark(&self) -> Option<u32> {
+        if self.tproxy_inbound().is_none() && self.tun_service().is_none() {
+            return None;
+        }
+        self.outbounds
+            .iter()
+            .find_map(|outbound| outbound.stream_settings.as_ref()?.sockopt.as_ref()?.mark)
+    }
+
+    /// Add or remove the dokodemo-door inbound and the outbound marks
+    pub fn set_tproxy(&mut self, setting: &TproxySetting) -> ConfigResult<()> {
+        if setting.enabled {
+            if setting.port == 0 || setting.mark == 0 {
+                return Err(ConfigError::InvalidInbound(
+                    "tproxy port and mark can not be 0".into(),
+                ));
+            }
+            let used = self
+                .inbounds
+                .iter()
+                .chain(self.inbound_detour.iter())
+                .find(|i| i.port == setting.port && i.tag != TPROXY_INBOUND_TAG);
+            if let Some(used) = used {
+                return Err(ConfigError::InvalidInbound(format!(
+                    "port {} is used by inbound {}",
+                    setting.port, used.tag
+                )));
+            }
+        }
+
+        // TUN keeps its mark when the transparent proxy is turned off
+        let mark = match setting.enabled {
+            true => Some(setting.mark),
+            false => self.tun_service().and_then(|_| self.outbound_mark()),
+        };
+        self.inbounds.retain(|i| i.tag != TPROXY_INBOUND_TAG);
+        for outbound in &mut self.outbounds {
+            set_mark(&mut outbound.stream_settings, mark);
+        }
+        if !setting.enabled {
+            return Ok(());
+        }
+        let network = match setting.mode {
+            TproxyMode::Tproxy => "tcp,udp",
+            TproxyMode::Redirect => "tcp",
+        };
+        self.inbounds.push(Inbound {
+            port: setting.port,
+            listen: Some("0.0.0.0".into()),
+            tag: TPROXY_INBOUND_TAG.into(),
+            protocol: "dokodemo-door".into(),
+            settings: InboundSettings {
+                network: Some(network.into()),
+                follow_redirect: Some(true),
+                ..InboundSettings::default()
+            },
+            sniffing: Some(Sniffing {
+                enabled: true,
+                dest_override: self.sniff_destinations(),
+                ..Sniffing::default()
+            }),
+            stream_settings: Some(StreamSettings {
+                network: "tcp".into(),
+                security: "none".into(),
+                sockopt: Some(Sockopt {
+                    tproxy: Some(setting.mode.as_str().into()),
+                    ..Sockopt::default()
+                }),
+                ..StreamSettings::default()
+            }),
+        });
+        Ok(())
+    }
+}
+
+/// Set or clear `sockopt.mark`, dropping settings left empty
+pub(crate) fn set_mark(stream: &mut Option<StreamSettings>, mark: Option<u32>) {
+    match (stream.as_mut(), mark) {
+        (Some(stream), _) => {
+            stream.sockopt.get_or_insert_default().mark = mark;
+            if stream.sockopt.as_ref() == Some(&Sockopt::default()) {
+                stream.sockopt = None;
+            }
+        }
+        (None, Some(mark)) => {
+            *stream = Some(StreamSettings {
+                network: "tcp".into(),
+                security: "none".into(),
+                sockopt: Some(Sockopt {
+                    mark: Some(mark),
+                    ..Sockopt::default()
+                }),
+                ..StreamSettings::default()
+            });
+        }
+        (None, None) => {}
+    }
+}
+
+/// Shell script installing firewall rules that send traffic to the tproxy inbound
+///
+/// Connections marked by the core, `BYPASS_RANGES` and `bypass` (e.g. node
+/// server IPs) are left alone. IPv4 only, review it before running as root.
+pub fn tproxy_script(setting: &TproxySetting, kind: FirewallKind, bypass: &[String]) -> String {
+    let TproxySetting {
+        port, mark, mode, ..
+    } = *setting;
+    let ranges = BYPASS_RANGES
+        .iter()
+        .map(|range| range.to_string())
+        .chain(bypass.iter().cloned())
+        .collect::<Vec<_>>();
+
+    let mut script = String::new();
+    let mut line = |text: String| {
+        script.push_str(&text);
+        script.push('\n');
+    };
+    line("#!/bin/sh".into());
+    line("# Transparent proxy rules generated by venus, review them before running as root".into());
+    line("set -e".into());
+    line(String::new());
+    if mode == TproxyMode::Tproxy {
+        line(format!(
+            "ip rule add fwmark {ROUTE_MARK} table {ROUTE_TABLE}"
+        ));
+        line(format!(
+            "ip route add local 0.0.0.0/0 dev lo table {ROUTE_TABLE}"
+        ));
+        line(String::new());
+    }
+
+    match kind {
+        FirewallKind::Nftables => {
+            let elements = ranges.join(", ");
+            let (hook, capture_in, capture_out) = match mode {
+                TproxyMode::Tproxy => (
+                    "filter hook prerouting priority mangle",
+                    format!(
+                        "meta l4proto {{ tcp, udp }} meta mark set {ROUTE_MARK} tproxy ip to 127.0.0.1:{port} accept"
+                    ),
+                    format!("meta l4proto {{ tcp, udp }} meta mark set {ROUTE_MARK}"),
+                ),
+                TproxyMode::Redirect => (
+                    "nat hook prerouting priority dstnat",
+                    format!("meta l4proto tcp redirect to :{port}"),
+                    format!("meta l4proto tcp redirect to :{port}"),
+                ),
+            };
+            let output_hook = match mode {
+                TproxyMode::Tproxy => "route hook output priority mangle",
+                TproxyMode::Redirect => "nat hook output priority -100",
+            };
+            line("nft -f - <<'EOF'".into());
+            line("table ip venus {".into());
+            line("    set bypass {".into());
+            line("        type ipv4_addr".into());
+            line("        flags interval".into());
+            line(format!("        elements = {{ {elements} }}"));
+            line("    }".into());
+            line("    chain prerouting {".into());
+            line(format!("        type {hook}; policy accept;"));
+            line("        ip daddr @bypass return".into());
+            line(format!("        {capture_in}"));
+            line("    }".into());
+            line("    chain output {".into());
+            line(format!("        type {output_hook}; policy accept;"));
+            line(format!("        meta mark {mark} return"));
+            line("        ip daddr @bypass return".into());
+            line(format!("        {capture_out}"));
+            line("    }".into());
+            line("}".into());
+            line("EOF".into());
+            line(String::new());
+            line("# undo: nft delete table ip venus".into());
+        }
+        FirewallKind::Iptables => {
+            let table = match mode {
+                TproxyMode::Tproxy => "mangle",
+                TproxyMode::Redirect => "nat",
+            };
+            let protocols: &[&str] = match mode {
+                TproxyMode::Tproxy => &["tcp", "udp"],
+                TproxyMode::Redirect => &["tcp"],
+            };
+            let ipt = format!("iptables -t {table}");
+            for chain in ["VENUS", "VENUS_OUT"] {
+                line(format!("{ipt} -N {chain}"));
+                if chain == "VENUS_OUT" {
+                    line(format!("{ipt} -A {chain} -m mark --mark {mark} -j RETURN"));
+                }
+                for range in &ranges {
+                    line(format!("{ipt} -A {chain} -d {range} -j RETURN"));
+                }
+                for protocol in protocols {
+                    let target = match (mode, chain) {
+                        (TproxyMode::Tproxy, "VENUS") => format!(
+                            "TPROXY --on-ip 127.0.0.1 --on-port {port} --tproxy-mark {ROUTE_MARK}"
+                        ),
+                        (TproxyMode::Tproxy, _) => format!("MARK --set-mark {ROUTE_MARK}"),
+                        (TproxyMode::Redirect, _) => format!("REDIRECT --to-ports {port}"),
+                    };
+                    line(format!("{ipt} -A {chain} -p {protocol} -j {target}"));
+                }
+            }
+            line(format!("{ipt} -A PREROUTING -j VENUS"));
+            line(format!("{ipt} -A OUTPUT -j VENUS_OUT"));
+            line(String::new());
+            line(format!(
+                "# undo: {ipt} -D PREROUTING -j VENUS; {ipt} -D OUTPUT -j VENUS_OUT; \
+                 {ipt} -F VENUS; {ipt} -X VENUS; {ipt} -F VENUS_OUT; {ipt} -X VENUS_OUT"
+            ));
+        }
+    }
+    if mode == TproxyMode::Tproxy {
+        line(format!(
+            "#       ip rule del fwmark {ROUTE_MARK} table {ROUTE_TABLE}; ip route del local 0.0.0.0/0 dev lo table {ROUTE_TABLE}"
+        ));
+    }
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::raw::default_core;
+
+    #[test]
+    fn test_set_tproxy() {
+        let mut core = default_core();
+        let original = core.clone();
+        assert!(!core.tproxy().enabled);
+
+        let setting = TproxySetting {
+            enabled: true,
+            ..TproxySetting::default()
+        };
+        core.set_tproxy(&setting).unwrap();
+        assert_eq!(core.tproxy(), setting);
+        assert_eq!(core.outbound_mark(), Some(DEFAULT_OUTBOUND_MARK));
+        let json = serde_json::to_value(&core).unwrap();
+        let inbound = json["inbounds"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|i| i["tag"] == TPROXY_INBOUND_TAG)
+            .unwrap();
+        assert_eq!(inbound["settings"]["followRedirect"], true);
+        assert_eq!(inbound["streamSettings"]["sockopt"]["tproxy"], "tproxy");
+
+        let taken = TproxySetting {
+            port: 10808,
+            ..setting.clone()
+        };
+        assert!(core.set_tproxy(&taken).is_err());
+
+        core.set_tproxy(&TproxySetting::default()).unwrap();
+        assert_eq!(core.outbound_mark(), None);
+        assert_eq!(core.inbounds, original.inbounds);
+    }
+
+    #[test]
+    fn test_tproxy_script() {
+        let setting = TproxySetting {
+            enabled: true,
+            ..TproxySetting::default()
+        };
+        let bypass = ["1.2.3.4".to_string()];
+        let nft = tproxy_script(&setting, FirewallKind::Nftables, &bypass);
+        assert!(nft.contains("tproxy ip to 127.0.0.1:12345"));
+        assert!(nft.contains("240.0.0.0/4, 255.255.255.255/32, 1.2.3.4 }"));
+        assert!(nft.contains("meta mark 255 return"));
+        assert!(nft.contains("ip rule add fwmark 1 table 100"));
+
+        let redirect = TproxySetting {
+            mode: TproxyMode::Redirect,
+            ..setting
+        };
+        let iptables = tproxy_script(&redirect, FirewallKind::Iptables, &bypass);
+        assert!(iptables.contains("iptables -t nat -A VENUS -d 1.2.3.4 -j RETURN"));
+        assert!(iptables.contains("iptables -t nat -A VENUS -p tcp -j REDIRECT --to-ports 12345"));
+        assert!(!iptables.contains("-p udp"));
+        assert!(!iptables.contains("ip rule"));
+    }
+}
@@ -0,0 +1,301 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{
+        cidr::parse_cidr,
+        error::{ConfigError, ConfigResult},
+        tproxy::{set_mark, BYPASS_RANGES},
+        types::{CoreConfig, Services, Tun, TunCidr, TunSniffing},
+    },
+    consts::{DEFAULT_OUTBOUND_MARK, TUN_INBOUND_TAG},
+};
+
+const DEFAULT_TUN_NAME: &str = "venus-tun";
+const DEFAULT_TUN_MTU: u32 = 1500;
+/// Kept out of the default fake DNS pool `198.18.0.0/15`
+const DEFAULT_TUN_IP: &str = "172.19.0.1/30";
+/// Policy routing table sending traffic into the device
+const TUN_ROUTE_TABLE: u32 = 101;
+/// Priority of the first `ip rule`, the others follow it
+const TUN_RULE_PRIORITY: u32 = 9000;
+/// IPv6 counterpart of `BYPASS_RANGES`, used when an IPv6 route is set
+const BYPASS_RANGES_V6: [&str; 4] = ["::1/128", "fc00::/7", "fe80::/10", "ff00::/8"];
+/// Linux `IFNAMSIZ` without the terminating nul
+const MAX_NAME_LEN: usize = 15;
+
+/// System-wide proxying through the core's TUN stack
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunSetting {
+    pub enabled: bool,
+    /// Device name
+    pub name: String,
+    pub mtu: u32,
+    /// Addresses of the stack, as CIDRs
+    pub ips: Vec<String>,
+    /// Destinations routed into the device, as CIDRs
+    pub routes: Vec<String>,
+    /// Sniff `http` and `tls` destinations
+    pub sniffing: bool,
+    /// SO_MARK set on every outbound
+    pub mark: u32,
+}
+
+impl Default for TunSetting {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: DEFAULT_TUN_NAME.into(),
+            mtu: DEFAULT_TUN_MTU,
+            ips: vec![DEFAULT_TUN_IP.into()],
+            routes: vec!["0.0.0.0/0".into()],
+            sniffing: true,
+            mark: DEFAULT_OUTBOUND_MARK,
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> ConfigError {
+    ConfigError::InvalidInbound(message.into())
+}
+
+fn tun_cidr(cidr: &str) -> ConfigResult<TunCidr> {
+    let cidr = parse_cidr(cidr).ok_or_else(|| invalid(format!("{cidr} is not a CIDR")))?;
+    Ok(TunCidr {
+        ip_addr: cidr.addr.to_string().into(),
+        prefix: cidr.prefix,
+    })
+}
+
+/// Interface name characters Linux accepts, it goes into a root shell script
+fn valid_name(name: &str) -> bool {
+    (1..=MAX_NAME_LEN).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+fn format_cidr(cidr: &TunCidr) -> String {
+    format!("{}/{}", cidr.ip_addr, cidr.prefix)
+}
+
+impl CoreConfig {
+    pub(crate) fn tun_service(&self) -> Option<&Tun> {
+        self.services.as_ref()?.tun.as_ref()
+    }
+
+    /// Current TUN setting
+    pub fn tun(&self) -> TunSetting {
+        let Some(tun) = self.tun_service() else {
+            return TunSetting::default();
+        };
+        TunSetting {
+            enabled: true,
+            name: tun.name.to_string(),
+            mtu: tun.mtu,
+            ips: tun.ips.iter().map(format_cidr).collect(),
+            routes: tun.routes.iter().map(format_cidr).collect(),
+            sniffing: tun.sniffing_settings.as_ref().is_some_and(|s| s.enabled),
+            mark: self.outbound_mark().unwrap_or(DEFAULT_OUTBOUND_MARK),
+        }
+    }
+
+    /// Add or remove the TUN service and the outbound marks
+    pub fn set_tun(&mut self, setting: &TunSetting) -> ConfigResult<()> {
+        let tun = if setting.enabled {
+            if !valid_name(&setting.name) {
+                return Err(invalid(format!(
+                    "tun name should be 1 to {MAX_NAME_LEN} of A-Z a-z 0-9 _ . -"
+                )));
+            }
+            if !(576..=65535).contains(&setting.mtu) || setting.mark == 0 {
+                return Err(invalid("tun mtu should be 576 to 65535, mark not 0"));
+            }
+            let ips = setting
+                .ips
+                .iter()
+                .map(|ip| tun_cidr(ip))
+                .collect::<ConfigResult<Vec<_>>>()?;
+            let routes = setting
+                .routes
+                .iter()
+                .map(|route| tun_cidr(route))
+                .collect::<ConfigResult<Vec<_>>>()?;
+            if ips.is_empty() || routes.is_empty() {
+                return Err(invalid("tun needs at least one ip and one route"));
+            }
+            Some(Tun {
+                name: setting.name.clone().into(),
+                mtu: setting.mtu,
+                tag: TUN_INBOUND_TAG.into(),
+                ips,
+                routes,
+                sniffing_settings: Some(TunSniffing {
+                    enabled: setting.sniffing,
+                    destination_override: self.sniff_destinations(),
+                }),
+                ..Tun::default()
+            })
+        } else {
+            None
+        };
+
+        // the transparent proxy keeps its mark when TUN is turned off
+        let mark = match tun {
+            Some(_) => Some(setting.mark),
+            None => self.tproxy_inbound().and_then(|_| self.outbound_mark()),
+        };
+        for outbound in &mut self.outbounds {
+            set_mark(&mut outbound.stream_settings, mark);
+        }
+        let services = self.services.get_or_insert_default();
+        services.tun = tun;
+        if services == &Services::default() {
+            self.services = None;
+        }
+        Ok(())
+    }
+}
+
+/// Shell script routing system traffic into the TUN device
+///
+/// Run it after the core created the device. Connections marked by the core,
+/// private ranges and `bypass` (e.g. node server IPs) keep the main table.
+/// A device name edited into `config.json` by hand is checked again here.
+pub fn tun_script(setting: &TunSetting, bypass: &[String]) -> ConfigResult<String> {
+    if !valid_name(&setting.name) {
+        return Err(invalid(format!("invalid tun name {:?}", setting.name)));
+    }
+    let TunSetting {
+        name, routes, mark, ..
+    } = setting;
+    let ip = |cidr: &str| if cidr.contains(':') { "ip -6" } else { "ip" };
+    let ipv6 = routes.iter().any(|route| route.contains(':'));
+    let bypass_v6 = if ipv6 { &BYPASS_RANGES_V6[..] } else { &[] };
+    let bypass = BYPASS_RANGES
+        .iter()
+        .map(|range| Cow::from(*range))
+        .chain(bypass.iter().map(|ip| Cow::from(ip.as_str())))
+        .chain(bypass_v6.iter().map(|range| Cow::from(*range)))
+        .collect::<Vec<_>>();
+
+    let mut lines = vec![
+        "#!/bin/sh".to_string(),
+        "# TUN routes generated by venus, review them before running as root".into(),
+        "# run it after the core created the device".into(),
+        "set -e".into(),
+        String::new(),
+        format!("ip link set dev {name} up"),
+    ];
+    for route in routes {
+        lines.push(format!(
+            "{} route add {route} dev {name} table {TUN_ROUTE_TABLE}",
+            ip(route)
+        ));
+    }
+    let mut priority = TUN_RULE_PRIORITY;
+    lines.push(format!(
+        "ip rule add fwmark {mark} lookup main priority {priority}"
+    ));
+    if ipv6 {
+        lines.push(format!(
+            "ip -6 rule add fwmark {mark} lookup main priority {priority}"
+        ));
+    }
+    for range in &bypass {
+        priority += 1;
+        lines.push(format!(
+            "{} rule add to {range} lookup main priority {priority}",
+            ip(range)
+        ));
+    }
+    priority += 1;
+    lines.push(format!(
+        "ip rule add lookup {TUN_ROUTE_TABLE} priority {priority}"
+    ));
+    if ipv6 {
+        lines.push(format!(
+            "ip -6 rule add lookup {TUN_ROUTE_TABLE} priority {priority}"
+        ));
+    }
+    lines.push(String::new());
+    if ipv6 {
+        // a priority only has the rules of the families it was added for
+        lines.push(format!(
+            "# undo: for p in $(seq {TUN_RULE_PRIORITY} {priority}); do \
+             ip rule del priority $p 2>/dev/null; ip -6 rule del priority $p 2>/dev/null; done; \
+             ip route flush table {TUN_ROUTE_TABLE}; ip -6 route flush table {TUN_ROUTE_TABLE}"
+        ));
+    } else {
+        lines.push(format!(
+            "# undo: for p in $(seq {TUN_RULE_PRIORITY} {priority}); do ip rule del priority $p; done; \
+             ip route flush table {TUN_ROUTE_TABLE}"
+        ));
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::raw::default_core;
+
+    #[test]
+    fn test_set_tun() {
+        let mut core = default_core();
+        let setting = TunSetting {
+            enabled: true,
+            ..TunSetting::default()
+        };
+        core.set_tun(&setting).unwrap();
+        assert_eq!(core.tun(), setting);
+        let json = serde_json::to_value(&core).unwrap();
+        let tun = &json["services"]["tun"];
+        assert_eq!(tun["ips"][0]["ipAddr"], "172.19.0.1");
+        assert_eq!(tun["ips"][0]["prefix"], 30);
+        assert_eq!(tun["tag"], TUN_INBOUND_TAG);
+
+        let invalid = TunSetting {
+            routes: vec!["0.0.0.0/33".into()],
+            ..setting.clone()
+        };
+        assert!(core.set_tun(&invalid).is_err());
+        for name in ["x;reboot", "tun 0", "$(id)", ""] {
+            let invalid = TunSetting {
+                name: name.into(),
+                ..setting.clone()
+            };
+            assert!(core.set_tun(&invalid).is_err(), "{name}");
+            assert!(tun_script(&invalid, &[]).is_err(), "{name}");
+        }
+
+        core.set_tun(&TunSetting::default()).unwrap();
+        assert_eq!(core.services, None);
+        assert_eq!(core.outbound_mark(), None);
+    }
+
+    #[test]
+    fn test_tun_script() {
+        let setting = TunSetting {
+            routes: vec!["0.0.0.0/0".into(), "::/0".into()],
+            ..TunSetting::default()
+        };
+        let script = tun_script(&setting, &["1.2.3.4".into()]).unwrap();
+        assert!(script.contains("ip route add 0.0.0.0/0 dev venus-tun table 101"));
+        assert!(script.contains("ip -6 route add ::/0 dev venus-tun table 101"));
+        assert!(script.contains("ip rule add fwmark 255 lookup main priority 9000"));
+        assert!(script.contains("ip rule add to 1.2.3.4 lookup main priority 9011"));
+        assert!(script.contains("ip -6 rule add fwmark 255 lookup main priority 9000"));
+        assert!(script.contains("ip -6 rule add to fc00::/7 lookup main priority 9013"));
+        assert!(script.contains("ip rule add lookup 101 priority 9016"));
+        assert!(script.contains("ip -6 rule add lookup 101 priority 9016"));
+        assert!(script.contains("ip -6 rule del priority $p"));
+        assert!(script.contains("ip -6 route flush table 101"));
+
+        let script = tun_script(&TunSetting::default(), &[]).unwrap();
+        assert!(!script.contains("ip -6"));
+        assert!(script.contains("ip rule add lookup 101 priority 9011"));
+    }
+}
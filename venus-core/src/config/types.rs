@@ -1,23 +1,38 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::{borrow::Cow, collections::HashMap, path::PathBuf};
+use std::{borrow::Cow, collections::BTreeMap, path::PathBuf};
 
-use crate::consts::VERSION;
+use crate::{
+    config::{migration::CONFIG_SCHEMA_VERSION, routing::RoutingMode},
+    consts::{PROXY_OUTBOUND_TAG, VERSION},
+};
 
 /// RUA config and frontend global state
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct VenusConfig {
+    /// Venus version that wrote the config
     pub version: Cow<'static, str>,
+    /// Layout version of the config, see `migration`
+    ///
+    /// Fields added after the first schema default when missing, so history
+    /// snapshots of older schemas still load.
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
     pub subscriptions: Vec<Subscription>,
     pub settings: RUABasicSetting,
     pub user: Option<RUAUser>,
 }
 
+fn current_schema_version() -> u32 {
+    CONFIG_SCHEMA_VERSION
+}
+
 impl Default for VenusConfig {
     fn default() -> Self {
         VenusConfig {
             version: VERSION.into(),
+            schema_version: CONFIG_SCHEMA_VERSION,
             subscriptions: vec![],
             settings: RUABasicSetting::default(),
             user: None,
@@ -51,6 +66,12 @@ pub struct RUABasicSetting {
     /// Current selected node id (node_id)
     pub current_id: Cow<'static, str>,
     pub logging: bool,
+    /// One-click routing preset
+    #[serde(default)]
+    pub routing_mode: RoutingMode,
+    /// Sources allowed to use the proxy inbounds when LAN access is on
+    #[serde(default)]
+    pub lan_allowlist: Vec<String>,
 }
 impl Default for RUABasicSetting {
     fn default() -> Self {
@@ -59,6 +80,8 @@ impl Default for RUABasicSetting {
             speed_url: "".into(),
             current_id: "".into(),
             logging: false,
+            routing_mode: RoutingMode::default(),
+            lan_allowlist: vec![],
         }
     }
 }
@@ -174,9 +197,33 @@ pub struct CoreConfig {
     pub routing: Routing,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dns: Option<Dns>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fakedns: Option<Vec<FakeDns>>,
     pub policy: Policy,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub other: Option<Other>,
+    /// v5 services, read by the v5 core from v4 json too
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub services: Option<Services>,
+}
+
+impl CoreConfig {
+    /// Address of the core's gRPC api inbound, e.g. `http://127.0.0.1:10086`
+    ///
+    /// Returns `None` when the api or its dokodemo-door inbound is not configured.
+    pub fn api_url(&self) -> Option<String> {
+        let api = self.api.as_ref()?;
+        let inbound = self
+            .inbounds
+            .iter()
+            .chain(self.inbound_detour.iter())
+            .find(|inbound| inbound.tag == api.tag)?;
+        let listen = match inbound.listen.as_deref() {
+            None | Some("0.0.0.0") | Some("") => "127.0.0.1",
+            Some(listen) => listen,
+        };
+        Some(format!("http://{}:{}", listen, inbound.port))
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -213,6 +260,8 @@ pub struct Inbound {
     // Traffic sniffing
     #[serde(skip_serializing_if = "Option::is_none")]
     pub sniffing: Option<Sniffing>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_settings: Option<StreamSettings>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -229,7 +278,22 @@ pub struct InboundSettings {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub allow_transparent: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<Cow<'static, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub follow_redirect: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub clients: Option<Vec<Client>>,
+    // for socks and http
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts: Option<Vec<Account>>,
+}
+
+/// Socks/http inbound user
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub user: Cow<'static, str>,
+    pub pass: Cow<'static, str>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -264,6 +328,63 @@ pub struct Outbound {
     pub mux: Option<Mux>,
 }
 
+/// Build the vmess proxy outbound from a subscription node
+impl From<&Node> for Outbound {
+    fn from(node: &Node) -> Self {
+        let tls = node.tls == "tls";
+        let network = if node.net.is_empty() {
+            "tcp".into()
+        } else {
+            node.net.clone()
+        };
+        let server_name = if node.sni.is_empty() {
+            node.host.clone()
+        } else {
+            node.sni.clone()
+        };
+        let stream_settings = StreamSettings {
+            security: if tls { "tls".into() } else { "none".into() },
+            tls_settings: tls.then(|| TlsSettings {
+                server_name,
+                alpn: node
+                    .alpn
+                    .split(',')
+                    .filter(|alpn| !alpn.is_empty())
+                    .map(|alpn| alpn.to_string().into())
+                    .collect(),
+                ..Default::default()
+            }),
+            ws_settings: (network == "ws").then(|| WsSettings {
+                path: node.path.clone(),
+                headers: WsHeaders {
+                    host: node.host.clone(),
+                },
+            }),
+            network,
+            ..Default::default()
+        };
+
+        Self {
+            protocol: "vmess".into(),
+            tag: PROXY_OUTBOUND_TAG.into(),
+            settings: OutboundSettings {
+                vnext: vec![Vmess {
+                    address: node.add.clone(),
+                    port: node.port.parse().unwrap_or_default(),
+                    users: vec![CoreUser {
+                        id: node.id.clone(),
+                        alter_id: node.aid.parse().unwrap_or_default(),
+                        email: "".into(),
+                        security: "auto".into(),
+                    }],
+                }],
+            },
+            stream_settings: Some(stream_settings),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StreamSettings {
@@ -411,9 +532,14 @@ pub struct QuicSettings {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Sockopt {
-    pub mark: i64,
-    pub tcp_fast_open: bool,
-    pub tproxy: Cow<'static, str>,
+    /// SO_MARK of outgoing connections
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mark: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp_fast_open: Option<bool>,
+    // "tproxy" | "redirect" | "off", for inbounds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tproxy: Option<Cow<'static, str>>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -488,6 +614,9 @@ pub struct Rule {
     pub outbound_tag: Cow<'static, str>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub balancer_tag: Option<Cow<'static, str>>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule_tag: Option<Cow<'static, str>>,
 }
 impl Rule {
     pub fn new(outbound_tag: Cow<'static, str>) -> Self {
@@ -504,6 +633,7 @@ impl Rule {
             attrs: None,
             outbound_tag,
             balancer_tag: None,
+            rule_tag: None,
         }
     }
 }
@@ -515,57 +645,102 @@ pub struct Balancers {
     pub selector: Vec<Cow<'static, str>>,
 }
 
+/// Built-in DNS, https://www.v2fly.org/config/dns.html
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Dns {
-    pub hosts: Hosts,
-    pub servers: (
-        Cow<'static, str>,
-        Servers,
-        Cow<'static, str>,
-        Cow<'static, str>,
-    ),
+    /// Static records, keyed by domain matcher like `domain:v2fly.org`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hosts: BTreeMap<String, HostAddress>,
+    #[serde(default)]
+    pub servers: Vec<DnsServer>,
+    #[serde(rename = "clientIp", skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<Cow<'static, str>>,
+    /// `UseIP`, `UseIPv4` or `UseIPv6`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_strategy: Option<Cow<'static, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_cache: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_fallback: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<Cow<'static, str>>,
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Hosts {
-    #[serde(rename = "domain:v2fly.org")]
-    pub domain_v2fly_org: Cow<'static, str>,
-    #[serde(rename = "domain:github.io")]
-    pub domain_github_io: Cow<'static, str>,
-    #[serde(rename = "domain:wikipedia.org")]
-    pub domain_wikipedia_org: Cow<'static, str>,
-    #[serde(rename = "domain:shadowsocks.org")]
-    pub domain_shadowsocks_org: Cow<'static, str>,
+/// Domain or IP a host resolves to, or several IPs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HostAddress {
+    One(Cow<'static, str>),
+    Many(Vec<Cow<'static, str>>),
+}
+
+/// A DNS server, either its address alone or with options
+///
+/// The address scheme picks the transport, e.g. `https://` for DoH,
+/// `tcp://`, `tls://`, `quic+local://` for DoQ, `fakedns` or `localhost`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DnsServer {
+    Address(Cow<'static, str>),
+    Server(DnsServerObject),
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Servers {
+pub struct DnsServerObject {
     pub address: Cow<'static, str>,
-    pub port: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub domains: Vec<Cow<'static, str>>,
+    #[serde(rename = "expectIPs", default, skip_serializing_if = "Vec::is_empty")]
+    pub expect_ips: Vec<Cow<'static, str>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_fallback: Option<bool>,
+}
+
+/// Pool fake IPs are handed out from, `app/dns/fakedns/fakedns.proto`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FakeDns {
+    pub ip_pool: Cow<'static, str>,
+    pub pool_size: u32,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Policy {
-    pub levels: HashMap<String, Levels>,
+    /// Keyed by user level, `"0"` applies to users without one
+    #[serde(default)]
+    pub levels: BTreeMap<String, Levels>,
+    #[serde(default)]
     pub system: System,
 }
 
+/// Limits of a user level, https://www.v2fly.org/config/policy.html
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Levels {
+    /// Seconds a connection has to finish its handshake
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handshake: Option<i64>,
+    /// Seconds an idle connection is kept open
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conn_idle: Option<i64>,
+    /// Seconds a connection is kept after the downlink closed
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uplink_only: Option<i64>,
+    /// Seconds a connection is kept after the uplink closed
     #[serde(skip_serializing_if = "Option::is_none")]
     pub downlink_only: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stats_user_uplink: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stats_user_downlink: Option<bool>,
+    /// Buffer of each connection in KB, 0 disables it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_size: Option<i64>,
 }
 
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -584,3 +759,47 @@ pub struct System {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Other {}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Services {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tun: Option<Tun>,
+}
+
+/// TUN device stack, `app/tun/config.proto`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tun {
+    /// Device name
+    pub name: Cow<'static, str>,
+    pub mtu: u32,
+    /// Inbound tag of connections from the device
+    pub tag: Cow<'static, str>,
+    /// Addresses of the stack
+    pub ips: Vec<TunCidr>,
+    /// Destinations the stack accepts
+    pub routes: Vec<TunCidr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_level: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_promiscuous_mode: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enable_spoofing: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sniffing_settings: Option<TunSniffing>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunCidr {
+    pub ip_addr: Cow<'static, str>,
+    pub prefix: u32,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunSniffing {
+    pub enabled: bool,
+    pub destination_override: Vec<Cow<'static, str>>,
+}
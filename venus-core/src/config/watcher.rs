@@ -0,0 +1,175 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::{backup::BackupKind, core_config_path, venus_config_path};
+
+/// How a config file on disk relates to the in-memory config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SyncStatus {
+    /// The file is what venus last read or wrote
+    Synced,
+    /// The file was edited outside venus, the in-memory config is unchanged
+    Modified,
+    /// The file was edited outside venus and the in-memory config changed as well
+    Conflict,
+}
+
+/// What venus last read from or wrote to a config file
+#[derive(Debug, Clone, Default)]
+pub struct FileSync {
+    /// File content
+    disk: Option<Vec<u8>>,
+    /// In-memory config serialized at the same time
+    memory: Option<String>,
+    /// Why the last reload of an external edit failed
+    pub error: Option<String>,
+}
+
+impl FileSync {
+    /// Remember the file content and the in-memory config matching it
+    pub fn synced(&mut self, disk: Vec<u8>, memory: String) {
+        self.disk = Some(disk);
+        self.memory = Some(memory);
+        self.error = None;
+    }
+
+    /// Forget the file content, the next write will not be refused
+    pub fn forget_disk(&mut self) {
+        self.disk = None;
+    }
+
+    /// Compare the current file and in-memory config with the synced ones
+    ///
+    /// A file never read or written by venus counts as synced.
+    pub fn status(&self, disk: &[u8], memory: &str) -> SyncStatus {
+        match &self.disk {
+            Some(synced) if synced != disk => {
+                if self.memory.as_deref() == Some(memory) {
+                    SyncStatus::Modified
+                } else {
+                    SyncStatus::Conflict
+                }
+            }
+            _ => SyncStatus::Synced,
+        }
+    }
+}
+
+/// Sync state of both config files
+#[derive(Debug, Clone, Default)]
+pub struct ConfigSync {
+    pub venus: FileSync,
+    pub core: FileSync,
+}
+
+impl ConfigSync {
+    pub fn get(&self, kind: BackupKind) -> &FileSync {
+        match kind {
+            BackupKind::Venus => &self.venus,
+            BackupKind::Core => &self.core,
+        }
+    }
+
+    pub fn get_mut(&mut self, kind: BackupKind) -> &mut FileSync {
+        match kind {
+            BackupKind::Venus => &mut self.venus,
+            BackupKind::Core => &mut self.core,
+        }
+    }
+}
+
+/// Sync status of a config file, for the api
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileSyncStatus {
+    pub kind: BackupKind,
+    pub status: SyncStatus,
+    pub error: Option<String>,
+}
+
+/// Absolute path of a config file, the file itself may not exist yet
+fn absolute_path(path: &Path) -> io::Result<PathBuf> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let file_name = path
+        .file_name()
+        .ok_or(io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+    Ok(fs::canonicalize(parent)?.join(file_name))
+}
+
+/// Watch both config files and send which one changed
+///
+/// The parent folders are watched instead of the files, editors usually
+/// replace a file by renaming, which drops a watch on the file itself.
+/// Events are not debounced, and venus's own writes are reported as well,
+/// compare with `Config::sync_status` before reloading.
+///
+/// The watcher stops when it is dropped.
+pub fn watch_config(tx: UnboundedSender<BackupKind>) -> notify::Result<RecommendedWatcher> {
+    let venus_path = absolute_path(&venus_config_path())?;
+    let core_path = absolute_path(&core_config_path())?;
+    let files = [
+        (venus_path.clone(), BackupKind::Venus),
+        (core_path.clone(), BackupKind::Core),
+    ];
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                warn!("config watcher error {err}");
+                return;
+            }
+        };
+        if !(event.kind.is_create() || event.kind.is_modify()) {
+            return;
+        }
+        for (path, kind) in &files {
+            if event.paths.contains(path) {
+                tx.send(*kind).ok();
+            }
+        }
+    })?;
+
+    let mut dirs = vec![];
+    for path in [&venus_path, &core_path] {
+        let dir = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+        if !dirs.contains(&dir) {
+            watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+            dirs.push(dir);
+        }
+    }
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_sync_status() {
+        let mut sync = FileSync::default();
+        // never read
+        assert_eq!(sync.status(b"a", "a"), SyncStatus::Synced);
+
+        sync.synced(b"a".to_vec(), "a".into());
+        assert_eq!(sync.status(b"a", "a"), SyncStatus::Synced);
+        // in-memory changes alone are written on the next write
+        assert_eq!(sync.status(b"a", "b"), SyncStatus::Synced);
+        assert_eq!(sync.status(b"b", "a"), SyncStatus::Modified);
+        assert_eq!(sync.status(b"b", "b"), SyncStatus::Conflict);
+
+        sync.forget_disk();
+        assert_eq!(sync.status(b"b", "b"), SyncStatus::Synced);
+    }
+}
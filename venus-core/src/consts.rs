@@ -1,4 +1,4 @@
-use std::{borrow::Cow, env, sync::LazyLock};
+use std::{borrow::Cow, env, sync::LazyLock, time::Duration};
 
 use log::warn;
 
@@ -27,6 +27,99 @@ pub static VENUS_V2RAY_PATH: LazyLock<Cow<'static, str>> = LazyLock::new(|| {
         .unwrap_or(DEFAULT_VENUS_V2RAY_PATH.into()).into()
 });
 
+/// Default download url of `geoip.dat`
+pub const DEFAULT_VENUS_GEOIP_URL: &str =
+    "https://github.com/v2fly/geoip/releases/latest/download/geoip.dat";
+/// Where `geoip.dat` updates are downloaded from, read from environment varable `VENUS_GEOIP_URL`
+pub static VENUS_GEOIP_URL: LazyLock<String> =
+    LazyLock::new(|| env::var("VENUS_GEOIP_URL").unwrap_or(DEFAULT_VENUS_GEOIP_URL.into()));
+
+/// Default download url of `geosite.dat`
+pub const DEFAULT_VENUS_GEOSITE_URL: &str =
+    "https://github.com/v2fly/domain-list-community/releases/latest/download/dlc.dat";
+/// Where `geosite.dat` updates are downloaded from, read from environment varable `VENUS_GEOSITE_URL`
+pub static VENUS_GEOSITE_URL: LazyLock<String> =
+    LazyLock::new(|| env::var("VENUS_GEOSITE_URL").unwrap_or(DEFAULT_VENUS_GEOSITE_URL.into()));
+
+/// File name prefix of custom site lists in `VENUS_V2RAY_PATH`
+pub const CUSTOM_SITE_PREFIX: &str = "custom-";
+
+/// Default seconds to wait for the core to exit after SIGTERM
+pub const DEFAULT_VENUS_CORE_STOP_TIMEOUT: u64 = 5;
+/// Grace period before the core is killed, read from environment varable `VENUS_CORE_STOP_TIMEOUT` in seconds
+pub static VENUS_CORE_STOP_TIMEOUT: LazyLock<Duration> = LazyLock::new(|| {
+    let secs = env::var("VENUS_CORE_STOP_TIMEOUT")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_VENUS_CORE_STOP_TIMEOUT);
+    Duration::from_secs(secs)
+});
+
+/// Default count of backups kept for each config file
+pub const DEFAULT_VENUS_CONFIG_BACKUPS: usize = 10;
+/// Count of backups kept for each config file, read from environment varable `VENUS_CONFIG_BACKUPS`
+pub static VENUS_CONFIG_BACKUPS: LazyLock<usize> = LazyLock::new(|| {
+    env::var("VENUS_CONFIG_BACKUPS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(DEFAULT_VENUS_CONFIG_BACKUPS)
+});
+
+/// Default count of config history snapshots kept
+pub const DEFAULT_VENUS_CONFIG_HISTORY: usize = 50;
+/// Count of config history snapshots kept, read from environment varable `VENUS_CONFIG_HISTORY`
+pub static VENUS_CONFIG_HISTORY: LazyLock<usize> = LazyLock::new(|| {
+    env::var("VENUS_CONFIG_HISTORY")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(DEFAULT_VENUS_CONFIG_HISTORY)
+});
+
+/// Whether config files are watched for edits made outside venus,
+/// read from environment varable `VENUS_CONFIG_WATCH`, defaults to `true`
+pub static VENUS_CONFIG_WATCH: LazyLock<bool> = LazyLock::new(|| {
+    env::var("VENUS_CONFIG_WATCH")
+        .ok()
+        .and_then(|watch| watch.parse().ok())
+        .unwrap_or(true)
+});
+
+/// Whether the running core is restarted after its config was edited outside venus,
+/// read from environment varable `VENUS_WATCH_RESTART`, defaults to `true`
+pub static VENUS_WATCH_RESTART: LazyLock<bool> = LazyLock::new(|| {
+    env::var("VENUS_WATCH_RESTART")
+        .ok()
+        .and_then(|restart| restart.parse().ok())
+        .unwrap_or(true)
+});
+
+/// Wait for more events before reloading, editors write a file in several steps
+pub const CONFIG_WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Tag of the outbound generated from the selected node
+pub const PROXY_OUTBOUND_TAG: &str = "proxy";
+/// Tag of the freedom outbound used by routing presets
+pub const DIRECT_OUTBOUND_TAG: &str = "direct";
+/// Tag of the blackhole outbound used by routing presets
+pub const BLOCKED_OUTBOUND_TAG: &str = "blocked";
+/// `ruleTag` of the rules generated by a routing mode, custom rules have none
+pub const PRESET_RULE_TAG: &str = "venus-preset";
+/// `ruleTag` of the rule blocking LAN sources outside the allowlist
+pub const LAN_RULE_TAG: &str = "venus-lan";
+/// Tag of the dokodemo-door inbound of the transparent proxy
+pub const TPROXY_INBOUND_TAG: &str = "tproxy";
+/// Tag of connections from the TUN device
+pub const TUN_INBOUND_TAG: &str = "tun";
+/// Default SO_MARK of the core's own connections while the transparent proxy
+/// or TUN is on, marked connections are never captured
+pub const DEFAULT_OUTBOUND_MARK: u32 = 255;
+
+/// How many core stderr lines are kept for `CoreStatus`
+pub const STDERR_TAIL_LINES: usize = 20;
+
+/// Wait before reconnecting the core's `FollowLog` stream
+pub const CORE_LOG_FOLLOW_RETRY: Duration = Duration::from_secs(1);
+
 #[cfg(test)]
 mod tests {
     use super::*;
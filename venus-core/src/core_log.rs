@@ -0,0 +1,352 @@
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+/// Timestamp format of v2ray log lines, e.g. `2025/01/02 15:04:05.123456`
+const TIMESTAMP_FORMAT: &str = "%Y/%m/%d %H:%M:%S%.f";
+
+/// Severity of a core log line
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CoreLogLevel {
+    Debug,
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+impl CoreLogLevel {
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "Debug" => Some(Self::Debug),
+            "Info" => Some(Self::Info),
+            "Warning" => Some(Self::Warning),
+            "Error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// Parse `log.loglevel` of core config, v2ray defaults to `warning`
+    pub fn from_loglevel(loglevel: &str) -> Self {
+        match loglevel {
+            "debug" => Self::Debug,
+            "info" => Self::Info,
+            "error" | "none" => Self::Error,
+            _ => Self::Warning,
+        }
+    }
+
+    /// Value of `log.loglevel` in core config
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+
+    fn from_u8(level: u8) -> Self {
+        match level {
+            0 => Self::Debug,
+            1 => Self::Info,
+            2 => Self::Warning,
+            _ => Self::Error,
+        }
+    }
+}
+
+/// Runtime log level and follow state, shared with the log reader tasks
+///
+/// While the core's `FollowLog` stream is connected, log lines the core
+/// also writes to stdout are dropped there, so they are not sent twice.
+#[derive(Debug, Clone)]
+pub struct LogFollow {
+    level: Arc<AtomicU8>,
+    following: Arc<AtomicBool>,
+}
+
+impl LogFollow {
+    pub fn new(level: CoreLogLevel) -> Self {
+        Self {
+            level: Arc::new(AtomicU8::new(level as u8)),
+            following: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn level(&self) -> CoreLogLevel {
+        CoreLogLevel::from_u8(self.level.load(Ordering::Relaxed))
+    }
+
+    pub fn set_level(&self, level: CoreLogLevel) {
+        self.level.store(level as u8, Ordering::Relaxed);
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.following.load(Ordering::Relaxed)
+    }
+
+    pub fn set_following(&self, following: bool) {
+        self.following.store(following, Ordering::Relaxed);
+    }
+
+    /// Whether the entry is at or above the current level
+    ///
+    /// Access log entries always pass, the core only writes them when
+    /// the access log is on, whatever the level.
+    pub fn allows(&self, entry: &CoreLogEntry) -> bool {
+        entry.access.is_some() || entry.level >= self.level()
+    }
+}
+
+/// Whether the core accepted or rejected a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccessStatus {
+    Accepted,
+    Rejected,
+}
+
+impl Display for AccessStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessStatus::Accepted => write!(f, "accepted"),
+            AccessStatus::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+
+/// Fields of a v2ray access log line
+///
+/// `from 127.0.0.1:52314 accepted tcp:example.com:443 [socks -> proxy]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessLog {
+    pub source: String,
+    pub destination: String,
+    pub inbound_tag: Option<String>,
+    pub outbound_tag: Option<String>,
+    pub status: AccessStatus,
+}
+
+/// One parsed line of v2ray core output
+///
+/// Lines that match neither the error log nor the access log format are kept
+/// as `Info` with the whole line as `message`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreLogEntry {
+    /// Local time written by the core
+    pub timestamp: Option<NaiveDateTime>,
+    pub level: CoreLogLevel,
+    /// Source package of the line, e.g. `app/dispatcher`
+    pub component: Option<String>,
+    pub message: String,
+    /// Present when the line is an access log
+    pub access: Option<AccessLog>,
+}
+
+impl Display for CoreLogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(access) = &self.access {
+            write!(
+                f,
+                "{} {} {}",
+                access.source, access.status, access.destination
+            )?;
+            if let (Some(inbound), Some(outbound)) = (&access.inbound_tag, &access.outbound_tag) {
+                write!(f, " [{inbound} -> {outbound}]")?;
+            }
+            if !self.message.is_empty() {
+                write!(f, " {}", self.message)?;
+            }
+            return Ok(());
+        }
+        match &self.component {
+            Some(component) => write!(f, "{component}: {}", self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl CoreLogEntry {
+    /// Parse a line of core stdout or stderr
+    ///
+    /// Handles the error log format
+    /// `2025/01/02 15:04:05 [Warning] [2865395845] app/dispatcher: message`
+    /// and the access log format
+    /// `2025/01/02 15:04:05 from 127.0.0.1:52314 accepted tcp:example.com:443 [in -> out]`.
+    pub fn parse(line: &str) -> Self {
+        let line = line.trim();
+        let (timestamp, rest) = split_timestamp(line);
+
+        if let Some(entry) = parse_error_log(rest).or_else(|| parse_access_log(rest)) {
+            return Self { timestamp, ..entry };
+        }
+        Self {
+            timestamp,
+            message: rest.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Split the leading `date time` off the line if present
+fn split_timestamp(line: &str) -> (Option<NaiveDateTime>, &str) {
+    let mut parts = line.splitn(3, ' ');
+    let (Some(date), Some(time)) = (parts.next(), parts.next()) else {
+        return (None, line);
+    };
+    match NaiveDateTime::parse_from_str(&format!("{date} {time}"), TIMESTAMP_FORMAT) {
+        Ok(timestamp) => (
+            Some(timestamp),
+            parts.next().unwrap_or_default().trim_start(),
+        ),
+        Err(_) => (None, line),
+    }
+}
+
+/// `[Level] [session] component: message`, the session id is optional
+fn parse_error_log(rest: &str) -> Option<CoreLogEntry> {
+    let (tag, rest) = rest.strip_prefix('[')?.split_once(']')?;
+    let level = CoreLogLevel::from_tag(tag)?;
+    let mut rest = rest.trim_start();
+    if let Some((session, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+        if session.chars().all(|c| c.is_ascii_digit()) {
+            rest = after.trim_start();
+        }
+    }
+
+    let (component, message) = match rest.split_once(": ") {
+        Some((component, message)) if !component.contains(char::is_whitespace) => {
+            (Some(component.to_string()), message)
+        }
+        _ => (None, rest),
+    };
+    Some(CoreLogEntry {
+        level,
+        component,
+        message: message.to_string(),
+        ..Default::default()
+    })
+}
+
+/// `[from] source accepted|rejected destination [inbound -> outbound] reason`
+fn parse_access_log(rest: &str) -> Option<CoreLogEntry> {
+    let rest = rest.strip_prefix("from ").unwrap_or(rest);
+    let mut parts = rest.splitn(4, ' ');
+    let source = parts.next()?;
+    let status = match parts.next()? {
+        "accepted" => AccessStatus::Accepted,
+        "rejected" => AccessStatus::Rejected,
+        _ => return None,
+    };
+    let destination = parts.next()?;
+    let mut rest = parts.next().unwrap_or_default().trim_start();
+
+    let mut inbound_tag = None;
+    let mut outbound_tag = None;
+    if let Some((detour, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+        // `>>` is written when the outbound was picked by the router
+        if let Some((inbound, outbound)) = detour
+            .split_once(" -> ")
+            .or_else(|| detour.split_once(" >> "))
+        {
+            inbound_tag = Some(inbound.trim().to_string());
+            outbound_tag = Some(outbound.trim().to_string());
+            rest = after.trim_start();
+        }
+    }
+
+    let level = match status {
+        AccessStatus::Accepted => CoreLogLevel::Info,
+        AccessStatus::Rejected => CoreLogLevel::Warning,
+    };
+    Some(CoreLogEntry {
+        level,
+        message: rest.to_string(),
+        access: Some(AccessLog {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            inbound_tag,
+            outbound_tag,
+            status,
+        }),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_error_log() {
+        let entry = CoreLogEntry::parse(
+            "2025/01/02 15:04:05.123456 [Warning] [2865395845] app/dispatcher: default route for tcp:example.com:443",
+        );
+        assert_eq!(entry.level, CoreLogLevel::Warning);
+        assert_eq!(entry.component.as_deref(), Some("app/dispatcher"));
+        assert_eq!(entry.message, "default route for tcp:example.com:443");
+        assert_eq!(
+            entry.timestamp.unwrap().to_string(),
+            "2025-01-02 15:04:05.123456"
+        );
+        assert!(entry.access.is_none());
+    }
+
+    #[test]
+    fn test_parse_access_log() {
+        let entry = CoreLogEntry::parse(
+            "2025/01/02 15:04:05 from 127.0.0.1:52314 accepted tcp:example.com:443 [socks >> proxy]",
+        );
+        let access = entry.access.unwrap();
+        assert_eq!(entry.level, CoreLogLevel::Info);
+        assert_eq!(access.source, "127.0.0.1:52314");
+        assert_eq!(access.destination, "tcp:example.com:443");
+        assert_eq!(access.inbound_tag.as_deref(), Some("socks"));
+        assert_eq!(access.outbound_tag.as_deref(), Some("proxy"));
+        assert_eq!(access.status, AccessStatus::Accepted);
+
+        let entry = CoreLogEntry::parse(
+            "2025/01/02 15:04:05 127.0.0.1:52315 rejected tcp:example.com:80 proxy/socks: unknown command",
+        );
+        assert_eq!(entry.level, CoreLogLevel::Warning);
+        assert_eq!(entry.access.unwrap().status, AccessStatus::Rejected);
+        assert_eq!(entry.message, "proxy/socks: unknown command");
+    }
+
+    #[test]
+    fn test_log_follow_level() {
+        let follow = LogFollow::new(CoreLogLevel::from_loglevel("warning"));
+        let entry = CoreLogEntry::parse("[Info] app/dispatcher: taking detour [proxy]");
+        assert!(!follow.allows(&entry));
+        let access = CoreLogEntry::parse(
+            "2025/01/02 15:04:05 from 127.0.0.1:52314 accepted tcp:example.com:443 [socks >> proxy]",
+        );
+        assert!(follow.allows(&access));
+        follow.set_level(CoreLogLevel::Debug);
+        assert!(follow.allows(&entry));
+        assert_eq!(follow.level().as_str(), "debug");
+    }
+
+    #[test]
+    fn test_parse_unknown_line() {
+        let entry =
+            CoreLogEntry::parse("V2Ray 5.16.1 (V2Fly, a community-driven edition of V2Ray.)");
+        assert_eq!(entry.level, CoreLogLevel::Info);
+        assert!(entry.timestamp.is_none());
+        assert_eq!(
+            entry.to_string(),
+            "V2Ray 5.16.1 (V2Fly, a community-driven edition of V2Ray.)"
+        );
+    }
+}
@@ -1,7 +1,8 @@
-use std::{fmt::Display, sync::mpsc::SendError};
+use std::fmt::Display;
 
 use log::error;
 use thiserror::Error;
+use tokio::sync::broadcast::error::SendError;
 
 use crate::{config::error::ConfigError, message::MessageType};
 
@@ -24,6 +25,12 @@ pub enum SubscriptionError {
 
     #[error("Empty subscription content: {0}")]
     EmptyContent(String),
+
+    #[error("Node not found: {0}")]
+    NodeNotFound(String),
+
+    #[error("Unsupported node type: {0}")]
+    UnsupportedNode(String),
 }
 
 #[derive(Debug, Error)]
@@ -42,6 +49,9 @@ pub enum VenusError {
     #[error("Failed to terminate core process: {0}")]
     ProcessTermination(String),
 
+    #[error("Core config test failed: {0}")]
+    CoreConfigTest(String),
+
     // 版本相关错误
     #[error("Failed to parse version from: {0}")]
     VersionParse(String),
@@ -63,7 +73,24 @@ pub enum VenusError {
     ThreadJoin(String),
 
     #[error("Channel send error: {0}")]
-    ChannelSend(#[from] SendError<MessageType>),
+    ChannelSend(#[from] Box<SendError<MessageType>>),
+
+    // 地理数据解析错误
+    #[error("Failed to decode geo data: {0}")]
+    GeoDecode(#[from] prost::DecodeError),
+
+    #[error("Geo data checksum mismatch, expected {expected}, got {actual}")]
+    GeoChecksum { expected: String, actual: String },
+
+    #[error("Invalid custom site list: {0}")]
+    InvalidCustomList(String),
+
+    #[error("Custom site list not found: {0}")]
+    CustomListNotFound(String),
+
+    // DNS 测试错误
+    #[error("Invalid domain: {0}")]
+    InvalidDomain(String),
 
     // 子进程流错误
     #[error("Child process stream unavailable")]
@@ -0,0 +1,186 @@
+use std::{fs, io, path::PathBuf};
+
+use prost::Message;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::backup::write_atomic,
+    consts::{CUSTOM_SITE_PREFIX, VENUS_V2RAY_PATH},
+    error::{VenusError, VenusResult},
+    proto::v2ray::core::app::router::routercommon::{domain, Domain, GeoSite, GeoSiteList},
+};
+
+/// A user-provided site list, compiled to a `geosite`-format file
+/// that rules reference as `ext:custom-<name>.dat:<name>`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomSiteList {
+    pub name: String,
+    /// Value to use in a rule's `domain` list
+    pub reference: String,
+    /// `domain:`, `full:`, `keyword:` or `regexp:` entries,
+    /// a bare domain matches as `domain:`
+    pub domains: Vec<String>,
+}
+
+fn check_name(name: &str) -> VenusResult<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        return Err(VenusError::InvalidCustomList(format!(
+            "name {name} may only contain a-z, 0-9, - and _"
+        )));
+    }
+    Ok(())
+}
+
+fn file_name(name: &str) -> String {
+    format!("{CUSTOM_SITE_PREFIX}{name}.dat")
+}
+
+fn custom_site_path(name: &str) -> PathBuf {
+    PathBuf::from(format!("{}/{}", *VENUS_V2RAY_PATH, file_name(name)))
+}
+
+/// Rule value matching the custom list `name`
+pub fn custom_site_reference(name: &str) -> String {
+    format!("ext:{}:{name}", file_name(name))
+}
+
+fn to_domain(entry: &str) -> VenusResult<Domain> {
+    let entry = entry.trim();
+    let (r#type, value) = match entry.split_once(':') {
+        Some(("domain", value)) => (domain::Type::RootDomain, value.to_lowercase()),
+        Some(("full", value)) => (domain::Type::Full, value.to_lowercase()),
+        Some(("keyword", value)) => (domain::Type::Plain, value.to_lowercase()),
+        Some(("regexp", value)) => {
+            Regex::new(value).map_err(|err| VenusError::InvalidCustomList(err.to_string()))?;
+            (domain::Type::Regex, value.to_string())
+        }
+        Some((prefix, _)) => {
+            return Err(VenusError::InvalidCustomList(format!(
+                "unknown prefix {prefix} in {entry}"
+            )))
+        }
+        None => (domain::Type::RootDomain, entry.to_lowercase()),
+    };
+    if value.is_empty() {
+        return Err(VenusError::InvalidCustomList(format!(
+            "empty entry {entry}"
+        )));
+    }
+    Ok(Domain {
+        r#type: r#type.into(),
+        value,
+        attribute: vec![],
+    })
+}
+
+fn from_domain(domain: &Domain) -> String {
+    let prefix = match domain.r#type() {
+        domain::Type::Plain => "keyword",
+        domain::Type::Regex => "regexp",
+        domain::Type::RootDomain => "domain",
+        domain::Type::Full => "full",
+    };
+    format!("{prefix}:{}", domain.value)
+}
+
+fn read_custom_sites(name: &str) -> VenusResult<CustomSiteList> {
+    let content = match fs::read(custom_site_path(name)) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(VenusError::CustomListNotFound(name.to_string()))
+        }
+        content => content?,
+    };
+    let domains = GeoSiteList::decode(content.as_slice())?
+        .entry
+        .iter()
+        .flat_map(|site| site.domain.iter().map(from_domain))
+        .collect();
+    Ok(CustomSiteList {
+        name: name.to_string(),
+        reference: custom_site_reference(name),
+        domains,
+    })
+}
+
+/// Custom site lists in `VENUS_V2RAY_PATH`, sorted by name
+pub fn list_custom_sites() -> VenusResult<Vec<CustomSiteList>> {
+    let mut lists = vec![];
+    for entry in fs::read_dir(VENUS_V2RAY_PATH.as_ref())? {
+        let file_name = entry?.file_name();
+        let name = file_name
+            .to_str()
+            .and_then(|f| f.strip_prefix(CUSTOM_SITE_PREFIX))
+            .and_then(|f| f.strip_suffix(".dat"));
+        if let Some(name) = name.filter(|name| check_name(name).is_ok()) {
+            lists.push(read_custom_sites(name)?);
+        }
+    }
+    lists.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(lists)
+}
+
+/// Create or replace the custom site list `name`
+///
+/// The core reads `ext:` files when it starts, restart it to use the new list.
+pub fn write_custom_sites(name: &str, domains: &[String]) -> VenusResult<CustomSiteList> {
+    check_name(name)?;
+    let domains = domains
+        .iter()
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| to_domain(entry))
+        .collect::<VenusResult<Vec<_>>>()?;
+    let list = GeoSiteList {
+        entry: vec![GeoSite {
+            country_code: name.to_uppercase(),
+            domain: domains,
+            ..GeoSite::default()
+        }],
+    };
+    write_atomic(&custom_site_path(name), &list.encode_to_vec())?;
+    read_custom_sites(name)
+}
+
+/// Delete the custom site list `name`
+pub fn remove_custom_sites(name: &str) -> VenusResult<()> {
+    check_name(name)?;
+    match fs::remove_file(custom_site_path(name)) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            Err(VenusError::CustomListNotFound(name.to_string()))
+        }
+        result => Ok(result?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_site_entries() {
+        for entry in [
+            "domain:example.com",
+            "full:a.example.com",
+            "keyword:ads",
+            r"regexp:^ad\.",
+        ] {
+            assert_eq!(from_domain(&to_domain(entry).unwrap()), entry);
+        }
+        assert_eq!(
+            from_domain(&to_domain(" Example.COM ").unwrap()),
+            "domain:example.com"
+        );
+        assert!(to_domain("geosite:cn").is_err());
+        assert!(to_domain("regexp:(").is_err());
+        assert!(to_domain("full:").is_err());
+
+        assert!(check_name("my-ads_2").is_ok());
+        assert!(check_name("../geoip").is_err());
+        assert_eq!(custom_site_reference("ads"), "ext:custom-ads.dat:ads");
+    }
+}
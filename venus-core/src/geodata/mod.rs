@@ -0,0 +1,384 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use prost::Message;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::{
+        cidr::Cidr,
+        error::{ConfigError, ConfigResult},
+        matcher::match_subdomain,
+        types::Rule,
+    },
+    consts::VENUS_V2RAY_PATH,
+    error::VenusResult,
+    proto::v2ray::core::app::router::routercommon::{
+        domain, Domain, GeoIp, GeoIpList, GeoSiteList,
+    },
+};
+
+pub mod custom;
+pub mod update;
+
+/// Path of `geoip.dat` the core reads
+pub fn geoip_path() -> PathBuf {
+    PathBuf::from(format!("{}/geoip.dat", *VENUS_V2RAY_PATH))
+}
+
+/// Path of `geosite.dat` the core reads
+pub fn geosite_path() -> PathBuf {
+    PathBuf::from(format!("{}/geosite.dat", *VENUS_V2RAY_PATH))
+}
+
+/// A `geosite:` or `geoip:` category
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoCategory {
+    /// Lowercase code as used in rules, e.g. `cn`
+    pub code: String,
+    /// Domains or CIDRs in the category
+    pub entries: usize,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Read a file, `None` when it does not exist
+fn read_optional(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Categories of `geosite.dat` and `geoip.dat`
+///
+/// A missing file leaves its categories empty, lookups against it
+/// return `None` as they cannot be answered.
+#[derive(Debug, Default)]
+pub struct GeoData {
+    /// Keyed by uppercase code, the way the core stores them
+    sites: Option<HashMap<String, Vec<Domain>>>,
+    ips: Option<HashMap<String, GeoIp>>,
+    /// Modification times of the loaded files
+    modified: (Option<SystemTime>, Option<SystemTime>),
+}
+
+impl GeoData {
+    /// Load `geosite.dat` and `geoip.dat` from `VENUS_V2RAY_PATH`
+    pub fn load() -> VenusResult<Self> {
+        let (site_path, ip_path) = (geosite_path(), geoip_path());
+        let mut geodata = Self::from_bytes(
+            read_optional(&site_path)?.as_deref(),
+            read_optional(&ip_path)?.as_deref(),
+        )?;
+        geodata.modified = (modified(&site_path), modified(&ip_path));
+        Ok(geodata)
+    }
+
+    /// Decode the protobuf content of `geosite.dat` and `geoip.dat`
+    pub fn from_bytes(site: Option<&[u8]>, ip: Option<&[u8]>) -> Result<Self, prost::DecodeError> {
+        let sites = site.map(GeoSiteList::decode).transpose()?.map(|list| {
+            list.entry
+                .into_iter()
+                .map(|site| (site.country_code.to_uppercase(), site.domain))
+                .collect()
+        });
+        let ips = ip.map(GeoIpList::decode).transpose()?.map(|list| {
+            list.entry
+                .into_iter()
+                .map(|ip| (ip.country_code.to_uppercase(), ip))
+                .collect()
+        });
+        Ok(Self {
+            sites,
+            ips,
+            modified: (None, None),
+        })
+    }
+
+    /// The files changed since they were loaded
+    pub fn is_stale(&self) -> bool {
+        self.modified != (modified(&geosite_path()), modified(&geoip_path()))
+    }
+
+    /// `geosite:` categories sorted by code
+    pub fn site_categories(&self) -> Vec<GeoCategory> {
+        let mut categories = self
+            .sites
+            .iter()
+            .flatten()
+            .map(|(code, domains)| GeoCategory {
+                code: code.to_lowercase(),
+                entries: domains.len(),
+            })
+            .collect::<Vec<_>>();
+        categories.sort_by(|a, b| a.code.cmp(&b.code));
+        categories
+    }
+
+    /// `geoip:` categories sorted by code
+    pub fn ip_categories(&self) -> Vec<GeoCategory> {
+        let mut categories = self
+            .ips
+            .iter()
+            .flatten()
+            .map(|(code, ip)| GeoCategory {
+                code: code.to_lowercase(),
+                entries: ip.cidr.len(),
+            })
+            .collect::<Vec<_>>();
+        categories.sort_by(|a, b| a.code.cmp(&b.code));
+        categories
+    }
+
+    /// Whether `geosite:<category>` contains `domain`
+    ///
+    /// `category` may filter by attribute, e.g. `google@cn`.
+    /// `None` when the file or the category is missing.
+    pub fn site_contains(&self, category: &str, domain: &str) -> Option<bool> {
+        let (code, attribute) = match category.split_once('@') {
+            Some((code, attribute)) => (code, Some(attribute)),
+            None => (category, None),
+        };
+        let domains = self.sites.as_ref()?.get(&code.to_uppercase())?;
+        let domain = domain.to_lowercase();
+        let contains = domains
+            .iter()
+            .filter(|d| attribute.is_none_or(|attr| d.attribute.iter().any(|a| a.key == attr)))
+            .any(|d| match_geo_domain(d, &domain));
+        Some(contains)
+    }
+
+    /// Whether `geoip:<category>` contains `ip`, `!cn` negates `cn`
+    ///
+    /// `None` when the file or the category is missing.
+    pub fn ip_contains(&self, category: &str, ip: IpAddr) -> Option<bool> {
+        let (negate, code) = match category.strip_prefix('!') {
+            Some(code) => (true, code),
+            None => (false, category),
+        };
+        let geoip = self.ips.as_ref()?.get(&code.to_uppercase())?;
+        let contains = geoip.cidr.iter().any(|cidr| {
+            let addr = match cidr.ip.len() {
+                4 => <[u8; 4]>::try_from(cidr.ip.as_slice())
+                    .map(IpAddr::from)
+                    .ok(),
+                16 => <[u8; 16]>::try_from(cidr.ip.as_slice())
+                    .map(IpAddr::from)
+                    .ok(),
+                _ => None,
+            };
+            addr.is_some_and(|addr| {
+                Cidr {
+                    addr,
+                    prefix: cidr.prefix,
+                }
+                .contains(ip)
+            })
+        });
+        Some(contains != (negate != geoip.inverse_match))
+    }
+
+    /// Categories containing a domain or IP, as rule values
+    ///
+    /// e.g. `["geosite:google", "geosite:geolocation-!cn"]`
+    pub fn lookup(&self, target: &str) -> Vec<String> {
+        let mut categories = match target.parse::<IpAddr>() {
+            Ok(ip) => self
+                .ip_categories()
+                .into_iter()
+                .filter(|c| self.ip_contains(&c.code, ip) == Some(true))
+                .map(|c| format!("geoip:{}", c.code))
+                .collect::<Vec<_>>(),
+            Err(_) => self
+                .site_categories()
+                .into_iter()
+                .filter(|c| self.site_contains(&c.code, target) == Some(true))
+                .map(|c| format!("geosite:{}", c.code))
+                .collect(),
+        };
+        categories.sort();
+        categories
+    }
+
+    /// Reject `geosite:` and `geoip:` categories missing from the files,
+    /// and `ext:` values whose file is not installed
+    ///
+    /// Categories are not checked for a file that is not installed.
+    pub fn validate_rule(&self, rule: &Rule) -> ConfigResult<()> {
+        let ips = || rule.ip.iter().chain(rule.source.iter()).flatten();
+        for value in rule.domain.iter().flatten().chain(ips()) {
+            check_ext_file(value)?;
+        }
+        if let Some(sites) = &self.sites {
+            for domain in rule.domain.iter().flatten() {
+                let Some(category) = domain.strip_prefix("geosite:") else {
+                    continue;
+                };
+                let code = category.split('@').next().unwrap_or(category);
+                if !sites.contains_key(&code.to_uppercase()) {
+                    return Err(ConfigError::InvalidRule(format!(
+                        "geosite category {code} not found"
+                    )));
+                }
+            }
+        }
+        if let Some(ips_data) = &self.ips {
+            for ip in ips() {
+                let Some(category) = ip.strip_prefix("geoip:") else {
+                    continue;
+                };
+                let code = category.strip_prefix('!').unwrap_or(category);
+                if !ips_data.contains_key(&code.to_uppercase()) {
+                    return Err(ConfigError::InvalidRule(format!(
+                        "geoip category {code} not found"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `ext:<file>:<category>` needs `<file>` in `VENUS_V2RAY_PATH`
+fn check_ext_file(value: &str) -> ConfigResult<()> {
+    let Some(file) = value
+        .strip_prefix("ext:")
+        .and_then(|ext| ext.split(':').next())
+    else {
+        return Ok(());
+    };
+    let path = Path::new(VENUS_V2RAY_PATH.as_ref()).join(file);
+    if !path.is_file() {
+        return Err(ConfigError::InvalidRule(format!(
+            "ext file {file} not found"
+        )));
+    }
+    Ok(())
+}
+
+fn match_geo_domain(entry: &Domain, domain: &str) -> bool {
+    let value = entry.value.as_str();
+    match entry.r#type() {
+        domain::Type::Plain => domain.contains(value),
+        domain::Type::Regex => Regex::new(value).is_ok_and(|re| re.is_match(domain)),
+        domain::Type::RootDomain => match_subdomain(domain, value),
+        domain::Type::Full => domain == value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::v2ray::core::app::router::routercommon::{domain::Attribute, Cidr, GeoSite};
+
+    fn geodata() -> GeoData {
+        let site = |code: &str, domains: Vec<Domain>| GeoSite {
+            country_code: code.into(),
+            domain: domains,
+            ..GeoSite::default()
+        };
+        let domain = |r#type: domain::Type, value: &str, attribute: Option<&str>| Domain {
+            r#type: r#type.into(),
+            value: value.into(),
+            attribute: attribute
+                .map(|key| Attribute {
+                    key: key.into(),
+                    ..Attribute::default()
+                })
+                .into_iter()
+                .collect(),
+        };
+        let sites = GeoSiteList {
+            entry: vec![
+                site(
+                    "GOOGLE",
+                    vec![
+                        domain(domain::Type::RootDomain, "google.com", None),
+                        domain(domain::Type::Full, "google.cn", Some("cn")),
+                    ],
+                ),
+                site("CN", vec![domain(domain::Type::Regex, r"\.cn$", None)]),
+            ],
+        };
+        let ips = GeoIpList {
+            entry: vec![GeoIp {
+                country_code: "PRIVATE".into(),
+                cidr: vec![Cidr {
+                    ip: vec![10, 0, 0, 0],
+                    prefix: 8,
+                    ..Cidr::default()
+                }],
+                ..GeoIp::default()
+            }],
+        };
+        GeoData::from_bytes(Some(&sites.encode_to_vec()), Some(&ips.encode_to_vec())).unwrap()
+    }
+
+    #[test]
+    fn test_geodata_lookup() {
+        let geodata = geodata();
+        assert_eq!(
+            geodata.site_categories(),
+            vec![
+                GeoCategory {
+                    code: "cn".into(),
+                    entries: 1
+                },
+                GeoCategory {
+                    code: "google".into(),
+                    entries: 2
+                },
+            ]
+        );
+        assert_eq!(
+            geodata.site_contains("google", "mail.google.com"),
+            Some(true)
+        );
+        assert_eq!(
+            geodata.site_contains("google@cn", "mail.google.com"),
+            Some(false)
+        );
+        assert_eq!(geodata.site_contains("google@cn", "google.cn"), Some(true));
+        assert_eq!(geodata.site_contains("missing", "google.cn"), None);
+        assert_eq!(
+            geodata.lookup("google.cn"),
+            vec!["geosite:cn", "geosite:google"]
+        );
+
+        let ip = "10.1.1.1".parse().unwrap();
+        assert_eq!(geodata.ip_contains("private", ip), Some(true));
+        assert_eq!(geodata.ip_contains("!private", ip), Some(false));
+        assert_eq!(geodata.lookup("10.1.1.1"), vec!["geoip:private"]);
+    }
+
+    #[test]
+    fn test_geodata_validate_rule() {
+        let geodata = geodata();
+        let rule = |domain: &str| Rule {
+            domain: Some(vec![domain.to_string().into()]),
+            ..Rule::new("direct".into())
+        };
+        assert!(geodata.validate_rule(&rule("geosite:google@cn")).is_ok());
+        assert!(geodata.validate_rule(&rule("geosite:gogle")).is_err());
+        let ip_rule = Rule {
+            ip: Some(vec!["geoip:!privat".into()]),
+            ..Rule::new("direct".into())
+        };
+        assert!(geodata.validate_rule(&ip_rule).is_err());
+        // nothing to check against
+        assert!(GeoData::default()
+            .validate_rule(&rule("geosite:gogle"))
+            .is_ok());
+    }
+}
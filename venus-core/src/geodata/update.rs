@@ -0,0 +1,153 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use log::info;
+use openssl::sha::sha256;
+use reqwest::header::USER_AGENT;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::backup::write_atomic,
+    consts::{NAME, VENUS_GEOIP_URL, VENUS_GEOSITE_URL, VERSION},
+    error::{VenusError, VenusResult},
+};
+
+use super::{geoip_path, geosite_path, GeoData};
+
+/// Geo data files the core ships with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeoKind {
+    Geoip,
+    Geosite,
+}
+
+impl GeoKind {
+    pub const ALL: [GeoKind; 2] = [GeoKind::Geoip, GeoKind::Geosite];
+
+    pub fn path(self) -> PathBuf {
+        match self {
+            GeoKind::Geoip => geoip_path(),
+            GeoKind::Geosite => geosite_path(),
+        }
+    }
+
+    /// Download url, its checksum is at `<url>.sha256sum`
+    pub fn url(self) -> &'static str {
+        match self {
+            GeoKind::Geoip => &VENUS_GEOIP_URL,
+            GeoKind::Geosite => &VENUS_GEOSITE_URL,
+        }
+    }
+
+    /// Check the content decodes as this kind of file
+    fn decode(self, content: &[u8]) -> Result<GeoData, prost::DecodeError> {
+        match self {
+            GeoKind::Geoip => GeoData::from_bytes(None, Some(content)),
+            GeoKind::Geosite => GeoData::from_bytes(Some(content), None),
+        }
+    }
+}
+
+/// An installed geo data file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeoFile {
+    pub kind: GeoKind,
+    /// Size in bytes, `0` when the file is missing
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    /// Where updates are downloaded from
+    pub url: String,
+}
+
+impl GeoFile {
+    pub fn stat(kind: GeoKind) -> Self {
+        let metadata = fs::metadata(kind.path()).ok();
+        Self {
+            kind,
+            size: metadata.as_ref().map_or(0, |m| m.len()),
+            modified: metadata
+                .and_then(|m| m.modified().ok())
+                .map(DateTime::<Utc>::from),
+            url: kind.url().to_string(),
+        }
+    }
+}
+
+/// Installed geoip and geosite files
+pub fn geo_files() -> Vec<GeoFile> {
+    GeoKind::ALL.into_iter().map(GeoFile::stat).collect()
+}
+
+/// Lowercase hex sha256 of `content`
+pub(crate) fn sha256_hex(content: &[u8]) -> String {
+    sha256(content).iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Check `content` against a `sha256sum` output, `<hex>  <file name>`
+fn verify_checksum(content: &[u8], checksum: &str) -> VenusResult<()> {
+    let expected = checksum
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    let actual = sha256_hex(content);
+    if expected != actual {
+        return Err(VenusError::GeoChecksum { expected, actual });
+    }
+    Ok(())
+}
+
+/// Download a geo data file and replace the installed one
+///
+/// The file is verified by its published sha256 checksum and must decode
+/// before it is swapped in. The core is not restarted.
+pub async fn download_geo(kind: GeoKind) -> VenusResult<GeoFile> {
+    let url = kind.url();
+    let client = reqwest::ClientBuilder::new()
+        .no_proxy()
+        .build()
+        .context("Failed to create HTTP client")?;
+    let get = |url: String| {
+        client
+            .get(url)
+            .header(USER_AGENT, format!("{NAME}/{VERSION}"))
+            .send()
+    };
+
+    let checksum = get(format!("{url}.sha256sum"))
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let content = get(url.to_string())
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    verify_checksum(&content, &checksum)?;
+    kind.decode(&content)?;
+
+    write_atomic(&kind.path(), &content)?;
+    info!("{kind:?} updated from {url}, {} bytes", content.len());
+    Ok(GeoFile::stat(kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_checksum() {
+        let content = b"geoip";
+        let checksum = format!("{}  geoip.dat\n", sha256_hex(content).to_uppercase());
+        assert!(verify_checksum(content, &checksum).is_ok());
+        assert!(matches!(
+            verify_checksum(b"geosite", &checksum),
+            Err(VenusError::GeoChecksum { .. })
+        ));
+        assert!(verify_checksum(content, "").is_err());
+    }
+}
@@ -3,5 +3,13 @@ pub enum GrpcError {
     #[error("grpc error: {0}")]
     Transport(#[from] tonic::transport::Error),
     #[error("grpc error: {0}")]
-    Status(#[from] tonic::Status),
+    Status(Box<tonic::Status>),
+    #[error("grpc error: unsupported {0}")]
+    Unsupported(String),
+}
+
+impl From<tonic::Status> for GrpcError {
+    fn from(status: tonic::Status) -> Self {
+        Self::Status(Box::new(status))
+    }
 }
@@ -0,0 +1,362 @@
+use std::net::IpAddr;
+
+use log::debug;
+
+use crate::config::types::{Outbound, StreamSettings};
+use crate::grpc::error::GrpcError;
+use crate::proto::typed_message;
+use crate::proto::v2ray::core::{
+    app::proxyman::{
+        command::{
+            handler_service_client::HandlerServiceClient, AddOutboundRequest, RemoveOutboundRequest,
+        },
+        MultiplexingConfig, SenderConfig,
+    },
+    common::{
+        net::{ip_or_domain::Address, IpOrDomain},
+        protocol::{SecurityConfig, SecurityType, ServerEndpoint, User},
+    },
+    proxy::{blackhole, freedom, vmess},
+    transport::internet::{tls, websocket, SocketConfig, StreamConfig, TransportConfig},
+    OutboundHandlerConfig,
+};
+
+/// Convert an address string into protobuf `IPOrDomain`
+fn ip_or_domain(address: &str) -> IpOrDomain {
+    let address = match address.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => Address::Ip(ip.octets().to_vec()),
+        Ok(IpAddr::V6(ip)) => Address::Ip(ip.octets().to_vec()),
+        Err(_) => Address::Domain(address.to_string()),
+    };
+    IpOrDomain {
+        address: Some(address),
+    }
+}
+
+/// Map vmess `security` from json config to protobuf `SecurityType`
+fn vmess_security(security: &str) -> SecurityType {
+    match security {
+        "aes-128-gcm" => SecurityType::Aes128Gcm,
+        "chacha20-poly1305" => SecurityType::Chacha20Poly1305,
+        "none" => SecurityType::None,
+        "zero" => SecurityType::Zero,
+        _ => SecurityType::Auto,
+    }
+}
+
+/// Convert `streamSettings` into protobuf `StreamConfig`
+///
+/// Only plain tcp and websocket with optional tls are supported, of the socket
+/// options only the mark. Any other set field returns `GrpcError::Unsupported`
+/// rather than a handler that differs from a restarted core.
+fn stream_config(stream: &StreamSettings) -> Result<StreamConfig, GrpcError> {
+    let mut config = StreamConfig::default();
+    match stream.network.as_ref() {
+        "" | "tcp" => {
+            let header = stream
+                .tcp_settings
+                .as_ref()
+                .map_or("none", |tcp| tcp.header.type_field.as_ref());
+            if !matches!(header, "" | "none") {
+                return Err(GrpcError::Unsupported(format!("tcp header {header}")));
+            }
+            config.protocol_name = "tcp".into();
+        }
+        "ws" | "websocket" => {
+            let ws = stream.ws_settings.clone().unwrap_or_default();
+            let mut header = vec![];
+            if !ws.headers.host.is_empty() {
+                header.push(websocket::Header {
+                    key: "Host".into(),
+                    value: ws.headers.host.to_string(),
+                });
+            }
+            let ws_config = websocket::Config {
+                path: ws.path.to_string(),
+                header,
+                ..Default::default()
+            };
+            config.protocol_name = "websocket".into();
+            config.transport_settings.push(TransportConfig {
+                protocol_name: "websocket".into(),
+                settings: Some(typed_message(
+                    "v2ray.core.transport.internet.websocket.Config",
+                    &ws_config,
+                )),
+                ..Default::default()
+            });
+        }
+        network => {
+            return Err(GrpcError::Unsupported(format!("network {network}")));
+        }
+    }
+
+    match stream.security.as_ref() {
+        "" | "none" => {}
+        "tls" => {
+            let tls_settings = stream.tls_settings.clone().unwrap_or_default();
+            if !tls_settings.certificates.is_empty() {
+                return Err(GrpcError::Unsupported("tls certificates".into()));
+            }
+            let tls_config = tls::Config {
+                allow_insecure: tls_settings.allow_insecure,
+                server_name: tls_settings.server_name.to_string(),
+                next_protocol: tls_settings.alpn.iter().map(|a| a.to_string()).collect(),
+                disable_system_root: tls_settings.disable_system_root,
+                ..Default::default()
+            };
+            let tls_type = "v2ray.core.transport.internet.tls.Config";
+            config.security_type = tls_type.into();
+            config
+                .security_settings
+                .push(typed_message(tls_type, &tls_config));
+        }
+        security => {
+            return Err(GrpcError::Unsupported(format!("security {security}")));
+        }
+    }
+    if let Some(sockopt) = &stream.sockopt {
+        if sockopt.tcp_fast_open.is_some() || sockopt.tproxy.is_some() {
+            return Err(GrpcError::Unsupported("sockopt".into()));
+        }
+        if let Some(mark) = sockopt.mark {
+            config.socket_settings = Some(SocketConfig {
+                mark,
+                ..Default::default()
+            });
+        }
+    }
+    Ok(config)
+}
+
+/// Convert our `Outbound` model into protobuf `OutboundHandlerConfig`
+///
+/// Supports `vmess`, `freedom` and `blackhole` outbounds. Anything else
+/// returns `GrpcError::Unsupported`, callers should fall back to restarting the core.
+pub fn outbound_handler_config(outbound: &Outbound) -> Result<OutboundHandlerConfig, GrpcError> {
+    let proxy_settings = match outbound.protocol.as_ref() {
+        "vmess" => {
+            let receiver = outbound
+                .settings
+                .vnext
+                .iter()
+                .map(|server| ServerEndpoint {
+                    address: Some(ip_or_domain(&server.address)),
+                    port: server.port.into(),
+                    user: server
+                        .users
+                        .iter()
+                        .map(|user| {
+                            let account = vmess::Account {
+                                id: user.id.to_string(),
+                                alter_id: user.alter_id.into(),
+                                security_settings: Some(SecurityConfig {
+                                    r#type: vmess_security(&user.security).into(),
+                                }),
+                                ..Default::default()
+                            };
+                            User {
+                                level: 0,
+                                email: user.email.to_string(),
+                                account: Some(typed_message(
+                                    "v2ray.core.proxy.vmess.Account",
+                                    &account,
+                                )),
+                            }
+                        })
+                        .collect(),
+                })
+                .collect();
+            typed_message(
+                "v2ray.core.proxy.vmess.outbound.Config",
+                &vmess::outbound::Config { receiver },
+            )
+        }
+        "freedom" => typed_message(
+            "v2ray.core.proxy.freedom.Config",
+            &freedom::Config::default(),
+        ),
+        "blackhole" => typed_message(
+            "v2ray.core.proxy.blackhole.Config",
+            &blackhole::Config::default(),
+        ),
+        protocol => return Err(GrpcError::Unsupported(format!("protocol {protocol}"))),
+    };
+
+    let sender = SenderConfig {
+        stream_settings: outbound
+            .stream_settings
+            .as_ref()
+            .map(stream_config)
+            .transpose()?,
+        multiplex_settings: outbound.mux.as_ref().map(|mux| MultiplexingConfig {
+            enabled: mux.enabled,
+            concurrency: mux.concurrency,
+        }),
+        ..Default::default()
+    };
+
+    Ok(OutboundHandlerConfig {
+        tag: outbound.tag.to_string(),
+        sender_settings: Some(typed_message(
+            "v2ray.core.app.proxyman.SenderConfig",
+            &sender,
+        )),
+        proxy_settings: Some(proxy_settings),
+        ..Default::default()
+    })
+}
+
+/// Replace the outbound with the same tag in the running core
+///
+/// The old handler is removed first, then the new one is added,
+/// so open connections of other outbounds are kept.
+///
+/// # Parameters
+/// * `url`: core api address, e.g. `http://127.0.0.1:10086`
+/// * `outbound`: the new outbound
+pub async fn replace_outbound(url: String, outbound: &Outbound) -> Result<(), GrpcError> {
+    let config = outbound_handler_config(outbound)?;
+    let mut client = HandlerServiceClient::connect(url).await?;
+    client
+        .remove_outbound(RemoveOutboundRequest {
+            tag: outbound.tag.to_string(),
+        })
+        .await?;
+    client
+        .add_outbound(AddOutboundRequest {
+            outbound: Some(config),
+        })
+        .await?;
+    debug!("outbound {} replaced", outbound.tag);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use super::*;
+    use crate::config::types::{
+        CoreUser, OutboundSettings, Sockopt, TlsSettings, Vmess, WsSettings,
+    };
+
+    fn vmess_outbound() -> Outbound {
+        Outbound {
+            protocol: "vmess".into(),
+            tag: "proxy".into(),
+            settings: OutboundSettings {
+                vnext: vec![Vmess {
+                    address: "example.com".into(),
+                    port: 443,
+                    users: vec![CoreUser {
+                        id: "66ad4540-b58c-4ad2-9926-ea63445a9b57".into(),
+                        alter_id: 0,
+                        email: "t@t.tt".into(),
+                        security: "auto".into(),
+                    }],
+                }],
+            },
+            stream_settings: Some(StreamSettings {
+                network: "ws".into(),
+                security: "tls".into(),
+                tls_settings: Some(TlsSettings {
+                    server_name: "example.com".into(),
+                    ..Default::default()
+                }),
+                ws_settings: Some(WsSettings {
+                    path: "/ray".into(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_vmess_outbound_handler_config() {
+        let config = outbound_handler_config(&vmess_outbound()).unwrap();
+        assert_eq!(config.tag, "proxy");
+
+        let proxy = config.proxy_settings.unwrap();
+        assert_eq!(
+            proxy.type_url,
+            "types.v2fly.org/v2ray.core.proxy.vmess.outbound.Config"
+        );
+        let vmess_config = vmess::outbound::Config::decode(proxy.value.as_slice()).unwrap();
+        let server = &vmess_config.receiver[0];
+        assert_eq!(server.port, 443);
+        assert_eq!(
+            server.address.as_ref().unwrap().address,
+            Some(Address::Domain("example.com".into()))
+        );
+
+        let sender =
+            SenderConfig::decode(config.sender_settings.unwrap().value.as_slice()).unwrap();
+        let stream = sender.stream_settings.unwrap();
+        assert_eq!(stream.protocol_name, "websocket");
+        assert_eq!(
+            stream.security_type,
+            "v2ray.core.transport.internet.tls.Config"
+        );
+        assert_eq!(stream.socket_settings, None);
+    }
+
+    #[test]
+    fn test_unsupported_outbound() {
+        let mut outbound = vmess_outbound();
+        outbound.protocol = "trojan".into();
+        assert!(matches!(
+            outbound_handler_config(&outbound),
+            Err(GrpcError::Unsupported(_))
+        ));
+
+        let mut outbound = vmess_outbound();
+        if let Some(stream) = outbound.stream_settings.as_mut() {
+            stream.network = "kcp".into();
+        }
+        assert!(matches!(
+            outbound_handler_config(&outbound),
+            Err(GrpcError::Unsupported(_))
+        ));
+
+        // set fields that can not be converted are not dropped
+        let mut outbound = vmess_outbound();
+        if let Some(stream) = outbound.stream_settings.as_mut() {
+            stream.sockopt = Some(Sockopt {
+                mark: Some(255),
+                tcp_fast_open: Some(true),
+                ..Default::default()
+            });
+        }
+        assert!(matches!(
+            outbound_handler_config(&outbound),
+            Err(GrpcError::Unsupported(_))
+        ));
+        let mut outbound = vmess_outbound();
+        if let Some(tls) = outbound
+            .stream_settings
+            .as_mut()
+            .and_then(|stream| stream.tls_settings.as_mut())
+        {
+            tls.certificates = vec!["cert".into()];
+        }
+        assert!(matches!(
+            outbound_handler_config(&outbound),
+            Err(GrpcError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_ip_or_domain() {
+        assert_eq!(
+            ip_or_domain("127.0.0.1").address,
+            Some(Address::Ip(vec![127, 0, 0, 1]))
+        );
+        assert_eq!(
+            ip_or_domain("example.com").address,
+            Some(Address::Domain("example.com".into()))
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use tonic::Streaming;
+
+use crate::grpc::error::GrpcError;
+use crate::proto::v2ray::core::app::log::command::{
+    logger_service_client::LoggerServiceClient, FollowLogRequest, FollowLogResponse,
+    RestartLoggerRequest,
+};
+
+/// Follow the core's log messages through `LoggerService`
+///
+/// Messages of every severity are sent regardless of `log.loglevel`,
+/// without the timestamp the core writes to its own outputs.
+///
+/// # Parameters
+/// * `url`: core api address, e.g. `http://127.0.0.1:10086`
+pub async fn follow_log(url: String) -> Result<Streaming<FollowLogResponse>, GrpcError> {
+    let mut client = LoggerServiceClient::connect(url).await?;
+    let stream = client.follow_log(FollowLogRequest {}).await?;
+    Ok(stream.into_inner())
+}
+
+/// Close and restart the core's logger, reopening its log files
+///
+/// Useful after the access or error log was rotated.
+///
+/// # Parameters
+/// * `url`: core api address, e.g. `http://127.0.0.1:10086`
+pub async fn restart_logger(url: String) -> Result<(), GrpcError> {
+    let mut client = LoggerServiceClient::connect(url).await?;
+    client.restart_logger(RestartLoggerRequest {}).await?;
+    Ok(())
+}
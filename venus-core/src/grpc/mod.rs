@@ -1,2 +1,5 @@
 pub mod error;
+pub mod handler;
+pub mod log;
+pub mod router;
 pub mod stats;
@@ -0,0 +1,60 @@
+use std::net::IpAddr;
+
+use crate::{
+    config::matcher::RouteQuery,
+    grpc::error::GrpcError,
+    proto::v2ray::core::{
+        app::router::command::{
+            routing_service_client::RoutingServiceClient, RoutingContext, TestRouteRequest,
+        },
+        common::net::Network,
+    },
+};
+
+fn ip_bytes(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+/// Ask the core's `RoutingService` which outbound `query` is routed to
+///
+/// Needs `RoutingService` in the core's `api.services`.
+///
+/// # Parameters
+/// * `url`: core api address, e.g. `http://127.0.0.1:10086`
+///
+/// # Returns
+/// * outbound tag
+pub async fn test_route(url: String, query: &RouteQuery) -> Result<String, GrpcError> {
+    let (target_ips, target_domain) = match query.target.parse::<IpAddr>() {
+        Ok(ip) => (vec![ip_bytes(ip)], String::new()),
+        Err(_) => (vec![], query.target.clone()),
+    };
+    let network = match query.network.as_deref() {
+        Some("udp") => Network::Udp,
+        _ => Network::Tcp,
+    };
+    let context = RoutingContext {
+        inbound_tag: query.inbound_tag.clone().unwrap_or_default(),
+        network: network.into(),
+        source_i_ps: query.source.map(ip_bytes).into_iter().collect(),
+        target_i_ps: target_ips,
+        target_port: query.port.unwrap_or_default().into(),
+        target_domain,
+        protocol: query.protocol.clone().unwrap_or_default(),
+        user: query.user.clone().unwrap_or_default(),
+        ..RoutingContext::default()
+    };
+
+    let mut client = RoutingServiceClient::connect(url).await?;
+    let response = client
+        .test_route(TestRouteRequest {
+            routing_context: Some(context),
+            field_selectors: vec!["outbound".into()],
+            publish_result: false,
+        })
+        .await?;
+    Ok(response.into_inner().outbound_tag)
+}
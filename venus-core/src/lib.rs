@@ -1,43 +1,70 @@
 use std::{
     env,
-    io::{self, BufRead, BufReader},
+    future::Future,
+    io::{self, Write},
     path::PathBuf,
-    process::{Child, Command, Stdio},
-    sync::mpsc::Sender,
-    thread,
+    process::{ExitStatus, Stdio},
+    sync::Arc,
+    time::Duration,
 };
 
-use anyhow::{anyhow, Context, Ok as AOk};
+use anyhow::Context;
 use base64::{engine::general_purpose, Engine};
 use chrono::Utc;
 use config::{
-    types::{Node, NodeType, Subscription},
+    backup::BackupKind,
+    error::{ConfigError, ConfigResult},
+    history::Snapshot,
+    matcher::{RouteMatch, RouteQuery},
+    raw::{parse_core_raw, ConfigIssue, IssueSource},
+    tproxy::set_mark,
+    types::{CoreConfig, Node, NodeType, Outbound, Subscription},
+    watcher::SyncStatus,
     Config,
 };
-use consts::{NAME, VENUS_V2RAY_PATH, VERSION};
+use consts::{
+    CORE_LOG_FOLLOW_RETRY, NAME, VENUS_CORE_STOP_TIMEOUT, VENUS_V2RAY_PATH, VENUS_WATCH_RESTART,
+    VERSION,
+};
+use core_log::{CoreLogEntry, CoreLogLevel, LogFollow};
 use error::{log_err, SubscriptionError, VenusError, VenusResult};
+use geodata::GeoData;
+use grpc::{handler::replace_outbound, log::follow_log, router};
+use log::{debug, info, warn};
 use message::MessageType;
 use reqwest::header::USER_AGENT;
+use status::{CoreState, CoreStatus, StderrTail};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::{Child, Command},
+    sync::broadcast::Sender,
+    time,
+};
 
 pub mod config;
 pub mod consts;
+pub mod core_log;
 pub mod error;
+pub mod geodata;
 pub mod grpc;
 pub mod message;
+pub mod proto;
+pub mod resolver;
+pub mod status;
 
 pub mod v2ray_core {
     tonic::include_proto!("v2ray.core.app.stats.command");
 }
 
 pub trait VenusCore {
-    /// Spawn a thread to execute v2ray core binary
-    fn spawn_core(&mut self) -> VenusResult<()>;
+    /// Spawn v2ray core binary, its output is forwarded to the message channel
+    fn spawn_core(&mut self) -> impl Future<Output = VenusResult<()>> + Send;
 
     /// Kill core process if exist
-    fn kill_core(&mut self) -> VenusResult<()>;
+    fn kill_core(&mut self) -> impl Future<Output = VenusResult<()>> + Send;
 
     /// Kill core and spawn new one
-    fn restart(&mut self) -> VenusResult<()>;
+    fn restart(&mut self) -> impl Future<Output = VenusResult<()>> + Send;
 }
 
 pub trait VenusSubscriptor {
@@ -53,7 +80,7 @@ pub trait VenusSubscriptor {
         &mut self,
         name: String,
         url: String,
-    ) -> impl std::future::Future<Output = VenusResult<()>> + Send;
+    ) -> impl Future<Output = VenusResult<()>> + Send;
 }
 
 #[derive(Debug)]
@@ -65,8 +92,16 @@ pub struct Venus {
     pub version: String,
     /// v2ray process
     child: Option<Child>,
-
-    /// message
+    /// v2ray process status
+    status: CoreStatus,
+    /// Last lines of v2ray stderr
+    stderr_tail: StderrTail,
+    /// Runtime log level of core output
+    log_follow: LogFollow,
+    /// Parsed `geosite.dat` and `geoip.dat`, loaded on first use
+    geodata: Option<Arc<GeoData>>,
+
+    /// message, every subscriber receives all core output
     message_tx: Sender<MessageType>,
 }
 
@@ -74,7 +109,7 @@ impl Venus {
     /// Create a new `Venus` instance
     ///
     /// # Parameters
-    /// * `message_tx`: message sender
+    /// * `message_tx`: message sender, subscribe to it to receive core output
     ///
     /// # Returns
     /// * `VenusResult<Self>`
@@ -84,28 +119,396 @@ impl Venus {
         let asset_path = PathBuf::from(VENUS_V2RAY_PATH.as_ref());
         env::set_var("V2RAY_LOCATION_ASSET", asset_path);
 
+        let loglevel = config
+            .core
+            .as_ref()
+            .and_then(|core| core.log.as_ref())
+            .map(|log| CoreLogLevel::from_loglevel(&log.loglevel))
+            .unwrap_or_default();
         Ok(Self {
             config,
             version: String::new(),
             child: None,
+            status: CoreStatus::default(),
+            stderr_tail: StderrTail::default(),
+            log_follow: LogFollow::new(loglevel),
+            geodata: None,
             message_tx,
         })
     }
+
+    /// Current core process status
+    ///
+    /// Polls the child process, so a core that exited on its own
+    /// is reported as `CoreState::Crashed`.
+    pub fn core_status(&mut self) -> CoreStatus {
+        if let Some(child) = self.child.as_mut() {
+            match child.try_wait() {
+                Ok(Some(exit)) => {
+                    self.status.state = CoreState::Crashed;
+                    self.status.last_exit_code = exit.code();
+                    self.status.pid = None;
+                    self.child = None;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    log_err(err);
+                }
+            }
+        }
+
+        let mut status = self.status.clone();
+        if status.state == CoreState::Running {
+            status.uptime = status
+                .started_at
+                .map(|started| (Utc::now() - started).num_seconds());
+        }
+        status.stderr_tail = self.stderr_tail.lines();
+        status
+    }
+
+    /// Validate the in-memory core config, write it to `config.json`
+    /// and (re)start the core with it
+    ///
+    /// When validation fails nothing is written and the core's error is returned.
+    /// When startup fails the last-known-good `config.json` is restored.
+    pub async fn apply_core(&mut self) -> VenusResult<()> {
+        let core_config = self.config.core.as_ref().ok_or(ConfigError::Empty(
+            "apply_core: v2ray core config is empty".into(),
+        ))?;
+        test_core_config(core_config).await?;
+
+        self.config.write_core()?;
+        self.start_written_core().await
+    }
+
+    /// Validate raw `config.json` text, write it as is and apply it
+    ///
+    /// Unlike `apply_core` comments and formatting are kept.
+    /// The core is (re)started only when `restart` is set.
+    pub async fn apply_core_raw(&mut self, raw: &str, restart: bool) -> VenusResult<()> {
+        let core_config = parse_core_raw(raw)?;
+        test_core_config(&core_config).await?;
+        self.config.write_core_raw(raw)?;
+        if let Some(log) = core_config.log.as_ref() {
+            self.log_follow
+                .set_level(CoreLogLevel::from_loglevel(&log.loglevel));
+        }
+        if restart {
+            self.start_written_core().await?;
+        }
+        Ok(())
+    }
+
+    /// Restore a config backup by its file name
+    ///
+    /// A core backup is checked by the core before it is written, then
+    /// applied like `apply_core_raw`. Nothing changes when it is rejected.
+    pub async fn restore_backup(&mut self, name: &str) -> VenusResult<BackupKind> {
+        let (kind, content) = self.config.read_backup(name)?;
+        match kind {
+            BackupKind::Venus => self.config.restore_venus_backup(&content)?,
+            BackupKind::Core => {
+                let raw = String::from_utf8_lossy(&content);
+                self.apply_core_raw(&raw, true).await?;
+            }
+        }
+        info!("config restored from backup {name}");
+        Ok(kind)
+    }
+
+    /// (Re)start the core with the written `config.json`
+    ///
+    /// Falls back to the last-known-good config when startup fails.
+    async fn start_written_core(&mut self) -> VenusResult<()> {
+        let started = if self.child.is_some() {
+            self.restart().await
+        } else {
+            self.spawn_core().await
+        };
+        if let Err(err) = started {
+            if self.config.restore_last_good_core()? {
+                self.spawn_core().await?;
+            }
+            return Err(err);
+        }
+
+        self.config.save_last_good_core()?;
+        Ok(())
+    }
+
+    /// Revert venus and core config to a history snapshot and apply it
+    ///
+    /// The snapshot's core config is validated before anything changes.
+    /// When writing or starting the core fails the previous configs are put back.
+    pub async fn revert(&mut self, id: u64) -> VenusResult<Snapshot> {
+        let snapshot = self.config.snapshot(id)?;
+        if let Some(core_config) = snapshot.core.as_ref() {
+            test_core_config(core_config).await?;
+        }
+        let previous = (self.config.venus.clone(), self.config.core.clone());
+        self.config.revert(&snapshot)?;
+        if snapshot.core.is_some() {
+            if let Err(err) = self.start_written_core().await {
+                self.config.undo_revert(previous);
+                return Err(err);
+            }
+        }
+        Ok(snapshot)
+    }
+
+    /// Change the in-memory core config and apply it
+    ///
+    /// `modify` should leave the config untouched when it returns an error.
+    /// When applying fails the in-memory config is put back.
+    pub async fn modify_core<T>(
+        &mut self,
+        modify: impl FnOnce(&mut CoreConfig) -> ConfigResult<T> + Send,
+    ) -> VenusResult<T> {
+        let core_config = self.config.core.as_mut().ok_or(ConfigError::Empty(
+            "modify_core: v2ray core config is empty".into(),
+        ))?;
+        let previous = core_config.clone();
+        let value = modify(core_config)?;
+        if let Err(err) = self.apply_core().await {
+            self.config.core = Some(previous);
+            return Err(err);
+        }
+        Ok(value)
+    }
+
+    /// Parsed geo data, reloaded when the files changed on disk
+    pub fn geodata(&mut self) -> VenusResult<Arc<GeoData>> {
+        match &self.geodata {
+            Some(geodata) if !geodata.is_stale() => Ok(geodata.clone()),
+            _ => {
+                let geodata = Arc::new(GeoData::load()?);
+                self.geodata = Some(geodata.clone());
+                Ok(geodata)
+            }
+        }
+    }
+
+    /// Pick up replaced geo data or custom site list files
+    ///
+    /// Drops the parsed geo data and restarts a running core,
+    /// which only reads these files when it starts.
+    pub async fn reload_geodata(&mut self) -> VenusResult<()> {
+        self.geodata = None;
+        if self.child.is_some() {
+            self.restart().await?;
+        }
+        Ok(())
+    }
+
+    /// Which rule and outbound a connection is routed to
+    ///
+    /// Matched offline against the routing rules and geo data, so it works
+    /// while the core is down. When the core is running its `TestRoute`
+    /// answer is added.
+    pub async fn test_route(&mut self, query: &RouteQuery) -> VenusResult<RouteMatch> {
+        let geodata = self
+            .geodata()
+            .map_err(|err| warn!("geo data unavailable for test_route: {err}"))
+            .ok();
+        let core_config = self.config.core.as_ref().ok_or(ConfigError::Empty(
+            "test_route: v2ray core config is empty".into(),
+        ))?;
+        let mut matched = core_config.match_route(query, geodata.as_deref());
+        if let (Some(url), true) = (core_config.api_url(), self.child.is_some()) {
+            match router::test_route(url, query).await {
+                Ok(tag) => matched.core_outbound_tag = Some(tag),
+                Err(err) => debug!("core TestRoute failed, using offline result only: {err}"),
+            }
+        }
+        Ok(matched)
+    }
+
+    /// Reload a config file edited outside venus
+    ///
+    /// A `SyncStatus::Conflict` file is only reloaded with `force`, discarding
+    /// in-memory changes. A core config is validated before it replaces
+    /// the in-memory one, then the running core is restarted with it when
+    /// `VENUS_WATCH_RESTART` is on.
+    ///
+    /// # Returns
+    /// * Sync status of the file before reloading
+    pub async fn reload_external(
+        &mut self,
+        kind: BackupKind,
+        force: bool,
+    ) -> VenusResult<SyncStatus> {
+        let status = self.config.sync_status(kind)?;
+        let reload = match status {
+            SyncStatus::Synced => false,
+            SyncStatus::Modified => true,
+            SyncStatus::Conflict => force,
+        };
+        if !reload {
+            return Ok(status);
+        }
+        let reloaded = match kind {
+            BackupKind::Venus => self.config.reload_rua().map_err(VenusError::from),
+            BackupKind::Core => self.reload_external_core().await,
+        };
+        if let Err(err) = &reloaded {
+            self.config.sync.get_mut(kind).error = Some(err.to_string());
+        }
+        reloaded.map(|_| status)
+    }
+
+    async fn reload_external_core(&mut self) -> VenusResult<()> {
+        let previous = (self.config.core.clone(), self.config.sync.core.clone());
+        self.config.reload_core()?;
+        let core_config = self.config.core.as_ref().ok_or(ConfigError::Empty(
+            "reload_external_core: v2ray core config is empty".into(),
+        ))?;
+        if let Err(err) = test_core_config(core_config).await {
+            // keep running with the previous config, the file stays modified
+            (self.config.core, self.config.sync.core) = previous;
+            return Err(err);
+        }
+        if let Some(log) = core_config.log.as_ref() {
+            self.log_follow
+                .set_level(CoreLogLevel::from_loglevel(&log.loglevel));
+        }
+
+        // falls back to the last-known-good config when the restart fails
+        if self.child.is_some() && *VENUS_WATCH_RESTART {
+            self.start_written_core().await?;
+        }
+        Ok(())
+    }
+
+    /// Replace (or add) an outbound and apply it to the running core
+    ///
+    /// An outbound that already exists in the running core is swapped live
+    /// through the core's `HandlerService`, keeping other connections open.
+    /// Falls back to `apply_core` (restart) when the api inbound is missing,
+    /// the outbound is new, or the gRPC call fails.
+    /// When applying fails the in-memory config is put back.
+    pub async fn apply_outbound(&mut self, outbound: Outbound) -> VenusResult<()> {
+        let previous = self.config.core.clone().ok_or(ConfigError::Empty(
+            "apply_outbound: v2ray core config is empty".into(),
+        ))?;
+        let applied = self.swap_outbound(outbound).await;
+        if applied.is_err() {
+            self.config.core = Some(previous);
+        }
+        applied
+    }
+
+    async fn swap_outbound(&mut self, outbound: Outbound) -> VenusResult<()> {
+        let core_config = self.config.core.as_mut().ok_or(ConfigError::Empty(
+            "apply_outbound: v2ray core config is empty".into(),
+        ))?;
+        // the core's own connections must stay out of the transparent proxy
+        let mut outbound = outbound;
+        if let Some(mark) = core_config.outbound_mark() {
+            set_mark(&mut outbound.stream_settings, Some(mark));
+        }
+        let existing = core_config
+            .outbounds
+            .iter()
+            .position(|o| o.tag == outbound.tag);
+        match existing {
+            Some(index) => core_config.outbounds[index] = outbound.clone(),
+            // the first outbound is the default one
+            None => core_config.outbounds.insert(0, outbound.clone()),
+        }
+
+        let api_url = core_config.api_url();
+        if let (Some(url), Some(_), true) = (api_url, existing, self.child.is_some()) {
+            test_core_config(core_config).await?;
+            match replace_outbound(url, &outbound).await {
+                Ok(()) => {
+                    self.config.write_core()?;
+                    self.config.save_last_good_core()?;
+                    return Ok(());
+                }
+                Err(err) => warn!("hot apply outbound failed, restarting core: {err}"),
+            }
+        }
+        self.apply_core().await
+    }
+
+    /// Current runtime log level of core output
+    pub fn log_level(&self) -> CoreLogLevel {
+        self.log_follow.level()
+    }
+
+    /// Change the core log level without restarting the core
+    ///
+    /// Followed logs are filtered by the new level right away, see `follow_core_log`.
+    /// The core keeps the level it was started with for its own outputs,
+    /// `log.loglevel` is written to `config.json` so the next start uses it too.
+    pub fn set_log_level(&mut self, level: CoreLogLevel) -> VenusResult<()> {
+        let core_config = self.config.core.as_mut().ok_or(ConfigError::Empty(
+            "set_log_level: v2ray core config is empty".into(),
+        ))?;
+        core_config.log.get_or_insert_default().loglevel = level.as_str().into();
+        self.config.write_core()?;
+        self.log_follow.set_level(level);
+        Ok(())
+    }
+
+    /// Forward the core's `FollowLog` stream to the message channel
+    ///
+    /// The stream carries every severity, so the runtime level works below
+    /// `log.loglevel` as well. It is reconnected after the core restarts,
+    /// until nobody subscribes to the message channel.
+    ///
+    /// # Returns
+    /// * `false` if the core config has no api inbound
+    pub fn follow_core_log(&self) -> bool {
+        let Some(url) = self.config.core.as_ref().and_then(|core| core.api_url()) else {
+            return false;
+        };
+        let tx = self.message_tx.clone();
+        let follow = self.log_follow.clone();
+        tokio::spawn(async move {
+            while tx.receiver_count() > 0 {
+                match follow_log(url.clone()).await {
+                    Ok(mut stream) => {
+                        follow.set_following(true);
+                        while let Ok(Some(res)) = stream.message().await {
+                            let entry = CoreLogEntry::parse(&res.message);
+                            if follow.allows(&entry) {
+                                tx.send(MessageType::Core(entry)).ok();
+                            }
+                        }
+                        follow.set_following(false);
+                    }
+                    // the api is gone while the core is stopped
+                    Err(err) => debug!("follow core log failed: {err}"),
+                }
+                time::sleep(CORE_LOG_FOLLOW_RETRY).await;
+            }
+        });
+        true
+    }
 }
 
 impl VenusCore for Venus {
-    /// Spawn a thread to execute v2ray core binary
-    fn spawn_core(&mut self) -> VenusResult<()> {
-        self.version = core_version()?;
+    /// Spawn v2ray core binary, its output is forwarded to the message channel
+    async fn spawn_core(&mut self) -> VenusResult<()> {
+        if self.child.is_some() {
+            return Err(VenusError::CoreLaunch("core already running".into()));
+        }
+        self.status.state = CoreState::Starting;
+        self.version = core_version().await.inspect_err(|_| {
+            self.status.state = CoreState::Stopped;
+        })?;
 
-        let core_exec_path = format!("{}/v2ray", &*VENUS_V2RAY_PATH);
+        let core_exec_path = format!("{}/v2ray", *VENUS_V2RAY_PATH);
         let mut child = Command::new(core_exec_path)
             .args(["run"])
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .spawn()?;
-
-        let tx = &self.message_tx;
+            .spawn()
+            .inspect_err(|_| {
+                self.status.state = CoreState::Stopped;
+            })?;
 
         let stdout = child.stdout.take().ok_or(io::Error::new(
             io::ErrorKind::UnexpectedEof,
@@ -115,63 +518,64 @@ impl VenusCore for Venus {
             io::ErrorKind::UnexpectedEof,
             "child stderr is empty",
         ))?;
-        let tx = tx.clone();
-        let child_handler = move || {
-            let stdout_tx = tx.clone();
-            let mut handlers = Vec::with_capacity(2);
-            let stdout_handler = thread::spawn(move || {
-                let mut lines = BufReader::new(stdout).lines();
-                lines.try_for_each(|line| {
-                    stdout_tx.send(MessageType::Core(line?))?;
-                    AOk(())
-                })?;
-                AOk(())
-            });
-            let stderr_tx = tx.clone();
-            let stderr_handler = thread::spawn(move || {
-                let mut lines = BufReader::new(stderr).lines();
-                lines.try_for_each(|line| {
-                    stderr_tx.send(MessageType::Core(line?))?;
-                    AOk(())
-                })?;
-                AOk(())
-            });
-
-            handlers.push(stdout_handler);
-            handlers.push(stderr_handler);
-            handlers
-                .into_iter()
-                .try_for_each(|handler| {
-                    handler
-                        .join()
-                        .map_err(|err| anyhow!("child join failed {err:?}"))??;
-                    AOk(())
-                })
-                .map_err(log_err)?;
-            AOk(())
-        };
-        thread::spawn(child_handler);
 
+        // sending only fails when nobody subscribed, the line is dropped then
+        let stdout_tx = self.message_tx.clone();
+        let follow = self.log_follow.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Some(line) = lines.next_line().await.map_err(log_err).ok().flatten() {
+                let entry = CoreLogEntry::parse(&line);
+                // timestamped lines are log messages, `follow_core_log` sends them while connected
+                let forward =
+                    entry.timestamp.is_none() || (!follow.is_following() && follow.allows(&entry));
+                if forward {
+                    stdout_tx.send(MessageType::Core(entry)).ok();
+                }
+            }
+        });
+        let stderr_tx = self.message_tx.clone();
+        self.stderr_tail.clear();
+        let stderr_tail = self.stderr_tail.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Some(line) = lines.next_line().await.map_err(log_err).ok().flatten() {
+                let entry = CoreLogEntry::parse(&line);
+                stderr_tail.push(line);
+                stderr_tx.send(MessageType::Core(entry)).ok();
+            }
+        });
+
+        self.status.state = CoreState::Running;
+        self.status.pid = child.id();
+        self.status.started_at = Some(Utc::now());
         self.child = Some(child);
         Ok(())
     }
 
     /// Kill core process if exist
-    fn kill_core(&mut self) -> VenusResult<()> {
-        if let Some(core) = self.child.as_mut() {
-            self.message_tx.send(MessageType::Terminate)?;
-            core.kill()?;
-            self.child = None;
-            Ok(())
-        } else {
-            Err(VenusError::CoreLaunch("core not running".into()))
-        }
+    ///
+    /// Sends SIGTERM first and waits `VENUS_CORE_STOP_TIMEOUT` for the core
+    /// to exit, then falls back to SIGKILL. The child is always reaped.
+    async fn kill_core(&mut self) -> VenusResult<()> {
+        let mut core = self.child.take().ok_or(VenusError::CoreNotRunning)?;
+        self.message_tx.send(MessageType::Terminate).ok();
+        let exit = terminate_child(&mut core, *VENUS_CORE_STOP_TIMEOUT).await?;
+        self.status.state = CoreState::Stopped;
+        self.status.pid = None;
+        self.status.started_at = None;
+        self.status.last_exit_code = exit.code();
+        Ok(())
     }
 
     /// Kill core and spawn new one
-    fn restart(&mut self) -> VenusResult<()> {
-        self.kill_core()?;
-        self.spawn_core()?;
+    async fn restart(&mut self) -> VenusResult<()> {
+        // a crashed core has nothing left to kill
+        if self.child.is_some() {
+            self.kill_core().await?;
+        }
+        self.spawn_core().await?;
+        self.status.restart_count += 1;
         Ok(())
     }
 }
@@ -195,12 +599,44 @@ impl VenusSubscriptor for Venus {
     }
 }
 
+/// Stop a child process gracefully
+///
+/// On unix the child gets SIGTERM and `timeout` to exit by itself,
+/// after that (or on other platforms) it is killed. Always waits for
+/// the child so no zombie is left behind.
+async fn terminate_child(child: &mut Child, timeout: Duration) -> VenusResult<ExitStatus> {
+    let termination = |e: io::Error| VenusError::ProcessTermination(e.to_string());
+
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        use nix::{
+            sys::signal::{kill, Signal},
+            unistd::Pid,
+        };
+
+        match kill(Pid::from_raw(pid as i32), Signal::SIGTERM) {
+            Ok(()) => match time::timeout(timeout, child.wait()).await {
+                Ok(exit) => return exit.map_err(termination),
+                Err(_) => warn!("core did not exit in {timeout:?} after SIGTERM, killing"),
+            },
+            Err(err) => warn!("send SIGTERM to core failed: {err}, killing"),
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = timeout;
+
+    // `kill` also waits for the child
+    child.kill().await.map_err(termination)?;
+    child.wait().await.map_err(termination)
+}
+
 /// Detect the v2ray core version
-pub fn core_version() -> VenusResult<String> {
-    let core_exec_path = format!("{}/v2ray", &*VENUS_V2RAY_PATH);
+pub async fn core_version() -> VenusResult<String> {
+    let core_exec_path = format!("{}/v2ray", *VENUS_V2RAY_PATH);
     let output = Command::new(core_exec_path)
         .args(["version"])
         .output()
+        .await
         .map_err(|e| VenusError::CoreLaunch(e.to_string()))?;
 
     let output_str = String::from_utf8_lossy(&output.stdout);
@@ -212,6 +648,62 @@ pub fn core_version() -> VenusResult<String> {
     Ok(version.to_string())
 }
 
+/// Run the core's config test mode (`v2ray test`) against a temp copy of `config`
+///
+/// Returns `VenusError::CoreConfigTest` with the core's output when the config is rejected.
+pub async fn test_core_config(config: &CoreConfig) -> VenusResult<()> {
+    // a file of its own per call, checks run concurrently with the editor's
+    let mut test_file = tempfile::Builder::new()
+        .prefix("venus-core-test-")
+        .suffix(".json")
+        .tempfile()?;
+    serde_json::to_writer_pretty(&mut test_file, config).map_err(ConfigError::from)?;
+    test_file.flush()?;
+
+    let core_exec_path = format!("{}/v2ray", *VENUS_V2RAY_PATH);
+    let output = Command::new(core_exec_path)
+        .args(["test", "-c"])
+        .arg(test_file.path())
+        .output()
+        .await;
+    test_file.close().map_err(log_err).ok();
+    let output = output.map_err(|e| VenusError::CoreLaunch(e.to_string()))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = if stderr.trim().is_empty() {
+        String::from_utf8_lossy(&output.stdout)
+    } else {
+        stderr
+    };
+    Err(VenusError::CoreConfigTest(message.trim().to_string()))
+}
+
+/// Check raw `config.json` text, for editor markers
+///
+/// The core's test mode only runs when the text parses.
+///
+/// # Returns
+/// * Found problems, empty when the config is valid
+pub async fn validate_core_raw(raw: &str) -> VenusResult<Vec<ConfigIssue>> {
+    let core_config = match parse_core_raw(raw) {
+        Ok(core_config) => core_config,
+        Err(err) => return Ok(vec![ConfigIssue::from(&err)]),
+    };
+    match test_core_config(&core_config).await {
+        Ok(()) => Ok(vec![]),
+        Err(VenusError::CoreConfigTest(message)) => Ok(vec![ConfigIssue {
+            source: IssueSource::Core,
+            line: None,
+            column: None,
+            message,
+        }]),
+        Err(err) => Err(err),
+    }
+}
+
 /// Send http request to download subscription info
 ///
 /// # Parameters
@@ -278,3 +770,39 @@ fn parse_node(line: &str, subs_name: &str, index: usize) -> Result<Node, Subscri
 
     Ok(node)
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_terminate_child_with_sigterm() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let exit = terminate_child(&mut child, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(
+            exit.signal(),
+            Some(nix::sys::signal::Signal::SIGTERM as i32)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_terminate_child_falls_back_to_kill() {
+        let mut child = Command::new("sh")
+            .args(["-c", "trap '' TERM; sleep 10"])
+            .spawn()
+            .unwrap();
+        // give the shell time to install the trap
+        time::sleep(Duration::from_millis(200)).await;
+        let exit = terminate_child(&mut child, Duration::from_millis(300))
+            .await
+            .unwrap();
+        assert_eq!(
+            exit.signal(),
+            Some(nix::sys::signal::Signal::SIGKILL as i32)
+        );
+    }
+}
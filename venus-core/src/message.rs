@@ -1,11 +1,19 @@
-use std::sync::mpsc::{Receiver, Sender};
+use tokio::sync::broadcast::{self, Sender};
 
-pub type Message = (Sender<MessageType>, Receiver<MessageType>);
+use crate::core_log::CoreLogEntry;
 
-#[derive(Debug)]
+/// How many messages a slow subscriber can fall behind before it starts lagging
+pub const MESSAGE_CAPACITY: usize = 1024;
+
+/// Create the message channel, receivers are created by `Sender::subscribe`
+pub fn channel() -> Sender<MessageType> {
+    broadcast::channel(MESSAGE_CAPACITY).0
+}
+
+#[derive(Debug, Clone)]
 pub enum MessageType {
-    /// Message from v2ray core process
-    Core(String),
+    /// Parsed output line of v2ray core process
+    Core(CoreLogEntry),
     /// Shutdown signal, the core process will exit
     Terminate,
 }
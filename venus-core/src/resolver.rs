@@ -0,0 +1,471 @@
+use std::{
+    fmt::Display,
+    io::{Read, Write},
+    net::{IpAddr, ToSocketAddrs},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hickory_proto::{
+    op::{Message, MessageType, OpCode, Query, ResponseCode},
+    rr::{Name, RData, RecordType},
+};
+use openssl::ssl::{SslConnector, SslMethod};
+use reqwest::header::{ACCEPT, CONTENT_TYPE, USER_AGENT};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{lookup_host, TcpStream, UdpSocket},
+    time::{timeout_at, Instant},
+};
+
+use crate::{
+    config::{
+        dns::{DnsProtocol, DnsServerEntry, QueryStrategy},
+        matcher::{match_cidr, match_domain},
+        types::CoreConfig,
+    },
+    consts::{NAME, VERSION},
+    error::{VenusError, VenusResult},
+    geodata::GeoData,
+};
+
+/// Time one server gets to answer a query
+const QUERY_TIMEOUT: Duration = Duration::from_secs(4);
+/// Time a whole test gets, kept under the API's request timeout
+const TEST_TIMEOUT: Duration = Duration::from_secs(12);
+const DNS_MESSAGE: &str = "application/dns-message";
+const HOSTS: &str = "hosts";
+
+/// What a domain resolved to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsTestResult {
+    pub domain: String,
+    /// Server that answered, `hosts` for a static record, `None` when none did
+    pub server: Option<String>,
+    pub ips: Vec<IpAddr>,
+    /// Milliseconds the answering server took
+    pub latency: Option<u64>,
+    /// Every server queried, in order
+    pub attempts: Vec<DnsAttempt>,
+}
+
+/// One server queried while resolving
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DnsAttempt {
+    pub server: String,
+    /// Milliseconds until it answered or failed
+    pub latency: u64,
+    pub ips: Vec<IpAddr>,
+    pub error: Option<String>,
+    /// Queried directly while the core sends it through the proxy, so the
+    /// core's answer may differ
+    pub direct: bool,
+}
+
+fn message(err: impl Display) -> String {
+    err.to_string()
+}
+
+/// Address of a server as written in the core config
+fn label(entry: &DnsServerEntry) -> String {
+    let address = &entry.address;
+    let local = if entry.local { "+local" } else { "" };
+    let scheme = match entry.protocol {
+        DnsProtocol::Fakedns => return "fakedns".into(),
+        DnsProtocol::Localhost => return "localhost".into(),
+        DnsProtocol::Udp => return format!("{address}:{}", entry.port.unwrap_or(53)),
+        DnsProtocol::Doh => return format!("https{local}://{address}"),
+        DnsProtocol::Tcp => "tcp",
+        DnsProtocol::Dot => "tls",
+        DnsProtocol::Doq => "quic",
+    };
+    match entry.port {
+        Some(port) => format!("{scheme}{local}://{address}:{port}"),
+        None => format!("{scheme}{local}://{address}"),
+    }
+}
+
+/// Static record of `domain` in `hosts`, plain keys match the full domain
+fn host_record<'a>(
+    hosts: &'a [(String, Vec<String>)],
+    domain: &str,
+    geo: Option<&GeoData>,
+) -> Option<&'a [String]> {
+    hosts.iter().find_map(|(pattern, addresses)| {
+        let matched = if pattern.contains(':') {
+            match_domain(domain, pattern, geo) == Some(true)
+        } else {
+            pattern.eq_ignore_ascii_case(domain)
+        };
+        matched.then_some(addresses.as_slice())
+    })
+}
+
+/// Servers in the order the core queries them
+///
+/// Servers whose `domains` match come first, the others follow
+/// unless fallback is disabled for them or for all.
+fn server_order<'a>(
+    servers: &'a [DnsServerEntry],
+    domain: &str,
+    disable_fallback: bool,
+    geo: Option<&GeoData>,
+) -> Vec<&'a DnsServerEntry> {
+    let (matched, others): (Vec<_>, Vec<_>) = servers.iter().partition(|server| {
+        server
+            .domains
+            .iter()
+            .any(|pattern| match_domain(domain, pattern, geo) == Some(true))
+    });
+    let fallback = others
+        .into_iter()
+        .filter(|server| !disable_fallback && !server.skip_fallback);
+    matched.into_iter().chain(fallback).collect()
+}
+
+fn record_types(strategy: QueryStrategy) -> &'static [RecordType] {
+    match strategy {
+        QueryStrategy::UseIp => &[RecordType::A, RecordType::AAAA],
+        QueryStrategy::UseIpv4 => &[RecordType::A],
+        QueryStrategy::UseIpv6 => &[RecordType::AAAA],
+    }
+}
+
+fn query_message(name: &Name, record_type: RecordType) -> Result<(u16, Vec<u8>), String> {
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.subsec_nanos() as u16);
+    let mut query = Message::new();
+    query
+        .set_id(id)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name.clone(), record_type));
+    Ok((id, query.to_vec().map_err(message)?))
+}
+
+fn answer_ips(id: u16, response: &[u8]) -> Result<Vec<IpAddr>, String> {
+    let response = Message::from_vec(response).map_err(message)?;
+    if response.id() != id {
+        return Err("answer does not match the query".into());
+    }
+    if response.response_code() != ResponseCode::NoError {
+        return Err(response.response_code().to_string());
+    }
+    Ok(response
+        .answers()
+        .iter()
+        .filter_map(|record| match record.data()? {
+            RData::A(a) => Some(IpAddr::V4(a.0)),
+            RData::AAAA(aaaa) => Some(IpAddr::V6(aaaa.0)),
+            _ => None,
+        })
+        .collect())
+}
+
+fn with_length(request: &[u8]) -> Vec<u8> {
+    let mut framed = (request.len() as u16).to_be_bytes().to_vec();
+    framed.extend_from_slice(request);
+    framed
+}
+
+/// Blocking TCP connection to the first address of `host` that answers in time
+fn connect_tls_host(host: &str, port: u16) -> Result<std::net::TcpStream, String> {
+    let mut last = "no address".to_string();
+    for address in (host, port).to_socket_addrs().map_err(message)? {
+        match std::net::TcpStream::connect_timeout(&address, QUERY_TIMEOUT) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last = err.to_string(),
+        }
+    }
+    Err(last)
+}
+
+/// DNS over TLS with openssl, the connection is blocking
+fn exchange_tls(host: &str, port: u16, request: &[u8]) -> Result<Vec<u8>, String> {
+    let stream = connect_tls_host(host, port)?;
+    stream
+        .set_read_timeout(Some(QUERY_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(QUERY_TIMEOUT)))
+        .map_err(message)?;
+    let connector = SslConnector::builder(SslMethod::tls())
+        .map_err(message)?
+        .build();
+    let mut stream = connector.connect(host, stream).map_err(message)?;
+    stream.write_all(&with_length(request)).map_err(message)?;
+    let mut length = [0; 2];
+    stream.read_exact(&mut length).map_err(message)?;
+    let mut response = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut response).map_err(message)?;
+    Ok(response)
+}
+
+/// Send one query and return the raw answer
+async fn exchange(entry: &DnsServerEntry, request: &[u8]) -> Result<Vec<u8>, String> {
+    let address = entry.address.as_str();
+    match entry.protocol {
+        DnsProtocol::Udp => {
+            let ip = address.parse::<IpAddr>().map_err(message)?;
+            let bind = if ip.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+            let socket = UdpSocket::bind(bind).await.map_err(message)?;
+            socket
+                .connect((ip, entry.port.unwrap_or(53)))
+                .await
+                .map_err(message)?;
+            socket.send(request).await.map_err(message)?;
+            let mut response = vec![0; 4096];
+            let len = socket.recv(&mut response).await.map_err(message)?;
+            response.truncate(len);
+            Ok(response)
+        }
+        DnsProtocol::Tcp => {
+            let mut stream = TcpStream::connect((address, entry.port.unwrap_or(53)))
+                .await
+                .map_err(message)?;
+            stream
+                .write_all(&with_length(request))
+                .await
+                .map_err(message)?;
+            let len = stream.read_u16().await.map_err(message)?;
+            let mut response = vec![0; len as usize];
+            stream.read_exact(&mut response).await.map_err(message)?;
+            Ok(response)
+        }
+        DnsProtocol::Doh => {
+            let client = reqwest::ClientBuilder::new()
+                .no_proxy()
+                .build()
+                .map_err(message)?;
+            let response = client
+                .post(format!("https://{address}"))
+                .header(USER_AGENT, format!("{NAME}/{VERSION}"))
+                .header(CONTENT_TYPE, DNS_MESSAGE)
+                .header(ACCEPT, DNS_MESSAGE)
+                .body(request.to_vec())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(message)?;
+            Ok(response.bytes().await.map_err(message)?.to_vec())
+        }
+        DnsProtocol::Dot => {
+            let (host, port) = (address.to_string(), entry.port.unwrap_or(853));
+            let request = request.to_vec();
+            tokio::task::spawn_blocking(move || exchange_tls(&host, port, &request))
+                .await
+                .map_err(message)?
+        }
+        DnsProtocol::Doq => Err("DNS over QUIC can not be tested from venus".into()),
+        DnsProtocol::Fakedns | DnsProtocol::Localhost => Err("not a DNS message server".into()),
+    }
+}
+
+/// Resolve `domain` with one server
+async fn query_server(
+    entry: &DnsServerEntry,
+    name: &Name,
+    strategy: QueryStrategy,
+) -> Result<Vec<IpAddr>, String> {
+    match entry.protocol {
+        DnsProtocol::Fakedns => {
+            return Err("skipped, fake DNS answers with IPs from its pool".into())
+        }
+        DnsProtocol::Localhost => {
+            let host = name.to_string();
+            let addresses = lookup_host((host.trim_end_matches('.'), 0))
+                .await
+                .map_err(message)?;
+            let types = record_types(strategy);
+            return Ok(addresses
+                .map(|address| address.ip())
+                .filter(|ip| match ip {
+                    IpAddr::V4(_) => types.contains(&RecordType::A),
+                    IpAddr::V6(_) => types.contains(&RecordType::AAAA),
+                })
+                .collect());
+        }
+        _ => {}
+    }
+    let mut ips = vec![];
+    for record_type in record_types(strategy) {
+        let (id, request) = query_message(name, *record_type)?;
+        let response = exchange(entry, &request).await?;
+        ips.extend(answer_ips(id, &response)?);
+    }
+    Ok(ips)
+}
+
+/// Resolve `domain` the way the core's DNS is configured
+///
+/// Static hosts are looked up first, then the servers in the core's order
+/// until one answers with IPs inside its `expectIPs`. Queries go out from
+/// the venus host directly, not through the proxy, attempts at servers the
+/// core reaches through the proxy are marked `direct`. DNS over QUIC or
+/// fake DNS servers are skipped. Servers left when `TEST_TIMEOUT` runs out
+/// are not tried.
+pub async fn test_dns(
+    core: &CoreConfig,
+    geo: Option<&GeoData>,
+    domain: &str,
+) -> VenusResult<DnsTestResult> {
+    let domain = domain.trim().trim_end_matches('.').to_lowercase();
+    if domain.is_empty() || domain.parse::<IpAddr>().is_ok() {
+        return Err(VenusError::InvalidDomain(domain));
+    }
+    let mut name =
+        Name::from_ascii(&domain).map_err(|_| VenusError::InvalidDomain(domain.clone()))?;
+    name.set_fqdn(true);
+
+    let setting = core.dns_setting();
+    let mut result = DnsTestResult {
+        domain: domain.clone(),
+        ..DnsTestResult::default()
+    };
+    let hosts = setting.hosts.into_iter().collect::<Vec<_>>();
+    if let Some(addresses) = host_record(&hosts, &domain, geo) {
+        let ips = addresses
+            .iter()
+            .filter_map(|address| address.parse::<IpAddr>().ok())
+            .collect::<Vec<_>>();
+        if !ips.is_empty() {
+            result.server = Some(HOSTS.into());
+            result.ips = ips;
+            result.latency = Some(0);
+            return Ok(result);
+        }
+        // a domain record is resolved in its place
+        if let Some(alias) = addresses.first() {
+            name = Name::from_ascii(alias).map_err(|_| VenusError::InvalidDomain(alias.clone()))?;
+            name.set_fqdn(true);
+        }
+    }
+
+    let disable_fallback = core
+        .dns
+        .as_ref()
+        .and_then(|dns| dns.disable_fallback)
+        .unwrap_or_default();
+    let localhost = [DnsServerEntry {
+        protocol: DnsProtocol::Localhost,
+        ..DnsServerEntry::default()
+    }];
+    let servers = match setting.servers.is_empty() {
+        // the core falls back to the system resolver without servers
+        true => localhost.iter().collect(),
+        false => server_order(&setting.servers, &domain, disable_fallback, geo),
+    };
+    let deadline = Instant::now() + TEST_TIMEOUT;
+    for server in servers {
+        let start = Instant::now();
+        if start >= deadline {
+            break;
+        }
+        let answer = timeout_at(
+            deadline.min(start + QUERY_TIMEOUT),
+            query_server(server, &name, setting.query_strategy),
+        )
+        .await
+        .unwrap_or_else(|_| Err("timed out".into()))
+        .and_then(|ips| {
+            let expected = ips
+                .into_iter()
+                .filter(|ip| {
+                    server.expect_ips.is_empty()
+                        || server
+                            .expect_ips
+                            .iter()
+                            .any(|cidr| match_cidr(*ip, cidr, geo) == Some(true))
+                })
+                .collect::<Vec<_>>();
+            match expected.is_empty() {
+                true => Err("no IP in the answer".to_string()),
+                false => Ok(expected),
+            }
+        });
+        let latency = start.elapsed().as_millis() as u64;
+        let mut attempt = DnsAttempt {
+            server: label(server),
+            latency,
+            direct: !server.local && server.protocol != DnsProtocol::Localhost,
+            ..DnsAttempt::default()
+        };
+        match answer {
+            Ok(ips) => {
+                attempt.ips = ips.clone();
+                result.server = Some(attempt.server.clone());
+                result.ips = ips;
+                result.latency = Some(latency);
+                result.attempts.push(attempt);
+                break;
+            }
+            Err(err) => {
+                attempt.error = Some(err);
+                result.attempts.push(attempt);
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::config::{dns::DnsSetting, raw::default_core};
+
+    fn server(address: &str, domains: &[&str], skip_fallback: bool) -> DnsServerEntry {
+        DnsServerEntry {
+            address: address.into(),
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+            skip_fallback,
+            ..DnsServerEntry::default()
+        }
+    }
+
+    #[test]
+    fn test_server_order() {
+        let servers = [
+            server("1.1.1.1", &[], false),
+            server("114.114.114.114", &["domain:baidu.com"], false),
+            server("8.8.8.8", &["keyword:google"], true),
+        ];
+        let order = |domain, disable_fallback| {
+            server_order(&servers, domain, disable_fallback, None)
+                .iter()
+                .map(|server| server.address.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            order("www.baidu.com", false),
+            ["114.114.114.114", "1.1.1.1"]
+        );
+        assert_eq!(order("www.google.com", true), ["8.8.8.8"]);
+        assert_eq!(order("example.com", false), ["1.1.1.1", "114.114.114.114"]);
+        assert_eq!(
+            label(&DnsServerEntry {
+                protocol: DnsProtocol::Dot,
+                address: "dns.google".into(),
+                ..DnsServerEntry::default()
+            }),
+            "tls://dns.google"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dns_hosts() {
+        let mut core = default_core();
+        let setting = DnsSetting {
+            hosts: BTreeMap::from([("venus.test".into(), vec!["10.0.0.1".into()])]),
+            ..core.dns_setting()
+        };
+        core.set_dns(&setting).unwrap();
+        let result = test_dns(&core, None, "Venus.Test.").await.unwrap();
+        assert_eq!(result.server.as_deref(), Some(HOSTS));
+        assert_eq!(result.ips, ["10.0.0.1".parse::<IpAddr>().unwrap()]);
+        assert!(test_dns(&core, None, "1.1.1.1").await.is_err());
+    }
+}
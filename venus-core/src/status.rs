@@ -0,0 +1,86 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::consts::STDERR_TAIL_LINES;
+
+/// Lifecycle state of the v2ray core process
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CoreState {
+    #[default]
+    Stopped,
+    Starting,
+    Running,
+    /// The process exited without being asked to
+    Crashed,
+}
+
+/// Runtime status of the v2ray core process
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoreStatus {
+    pub state: CoreState,
+    pub pid: Option<u32>,
+    pub started_at: Option<DateTime<Utc>>,
+    /// Seconds since `started_at`, only present while running
+    pub uptime: Option<i64>,
+    /// How many times the core has been restarted
+    pub restart_count: u32,
+    pub last_exit_code: Option<i32>,
+    /// Last lines the core wrote to stderr
+    pub stderr_tail: Vec<String>,
+}
+
+/// Last `STDERR_TAIL_LINES` lines of core stderr, shared with the reader task
+#[derive(Debug, Default, Clone)]
+pub struct StderrTail(Arc<Mutex<VecDeque<String>>>);
+
+impl StderrTail {
+    pub fn push(&self, line: String) {
+        let Ok(mut tail) = self.0.lock() else {
+            return;
+        };
+        if tail.len() >= STDERR_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.0
+            .lock()
+            .map(|tail| tail.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut tail) = self.0.lock() {
+            tail.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stderr_tail_keeps_last_lines() {
+        let tail = StderrTail::default();
+        for i in 0..STDERR_TAIL_LINES + 5 {
+            tail.push(i.to_string());
+        }
+        let lines = tail.lines();
+        assert_eq!(lines.len(), STDERR_TAIL_LINES);
+        assert_eq!(lines[0], "5");
+        assert_eq!(lines.last().unwrap(), &(STDERR_TAIL_LINES + 4).to_string());
+
+        tail.clear();
+        assert!(tail.lines().is_empty());
+    }
+}
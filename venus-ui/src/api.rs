@@ -52,6 +52,13 @@ pub enum RequestApi {
     Login,
     AddSubscription,
     ListSubscriptions,
+    Logs,
+    LogsStream,
+    CoreRaw,
+    CoreRawValidate,
+    Policy,
+    Tun,
+    TunScript,
 }
 impl fmt::Display for RequestApi {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -59,6 +66,13 @@ impl fmt::Display for RequestApi {
             Self::Login => write!(f, "/api/user/login"),
             Self::AddSubscription => write!(f, "/api/subscription/add"),
             Self::ListSubscriptions => write!(f, "/api/subscription/list"),
+            Self::Logs => write!(f, "/api/logs"),
+            Self::LogsStream => write!(f, "/api/logs/stream"),
+            Self::CoreRaw => write!(f, "/api/config/core/raw"),
+            Self::CoreRawValidate => write!(f, "/api/config/core/raw/validate"),
+            Self::Policy => write!(f, "/api/policy"),
+            Self::Tun => write!(f, "/api/tun"),
+            Self::TunScript => write!(f, "/api/tun/script"),
         }
     }
 }
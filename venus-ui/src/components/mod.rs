@@ -6,3 +6,4 @@ pub mod title;
 
 // 页面下的专用组件
 pub mod home_page;
+pub mod settings_page;
@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+
+use gloo::net::http::Method;
+use leptos::{ev, logging, prelude::*, task::spawn_local};
+use serde::{Deserialize, Serialize};
+use thaw::{ToastIntent, ToasterInjection};
+
+use crate::{
+    api::{axios, BaseResponse, RequestApi},
+    hooks::{dispatch_toast, use_global_user},
+    utils::error_to_string,
+};
+
+/// Limits of a user level, unset values use the core defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Levels {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handshake: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conn_idle: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uplink_only: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub downlink_only: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_user_uplink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_user_downlink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_size: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct System {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_inbound_uplink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_inbound_downlink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_outbound_uplink: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_outbound_downlink: Option<bool>,
+}
+
+/// Core policy, levels are keyed by user level
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Policy {
+    pub levels: BTreeMap<String, Levels>,
+    pub system: System,
+}
+
+/// A number field of a level, `unit` is shown after the input
+struct LevelField {
+    label: &'static str,
+    unit: &'static str,
+    get: fn(&Levels) -> Option<i64>,
+    set: fn(&mut Levels, Option<i64>),
+}
+
+static LEVEL_FIELDS: [LevelField; 5] = [
+    LevelField {
+        label: "Handshake",
+        unit: "s",
+        get: |l| l.handshake,
+        set: |l, v| l.handshake = v,
+    },
+    LevelField {
+        label: "Idle",
+        unit: "s",
+        get: |l| l.conn_idle,
+        set: |l, v| l.conn_idle = v,
+    },
+    LevelField {
+        label: "Uplink only",
+        unit: "s",
+        get: |l| l.uplink_only,
+        set: |l, v| l.uplink_only = v,
+    },
+    LevelField {
+        label: "Downlink only",
+        unit: "s",
+        get: |l| l.downlink_only,
+        set: |l, v| l.downlink_only = v,
+    },
+    LevelField {
+        label: "Buffer",
+        unit: "KB",
+        get: |l| l.buffer_size,
+        set: |l, v| l.buffer_size = v,
+    },
+];
+
+/// System stats switches, label and field
+type SystemField = (
+    &'static str,
+    fn(&System) -> Option<bool>,
+    fn(&mut System, bool),
+);
+
+static SYSTEM_FIELDS: [SystemField; 4] = [
+    (
+        "Inbound uplink stats",
+        |s| s.stats_inbound_uplink,
+        |s, v| s.stats_inbound_uplink = Some(v),
+    ),
+    (
+        "Inbound downlink stats",
+        |s| s.stats_inbound_downlink,
+        |s, v| s.stats_inbound_downlink = Some(v),
+    ),
+    (
+        "Outbound uplink stats",
+        |s| s.stats_outbound_uplink,
+        |s, v| s.stats_outbound_uplink = Some(v),
+    ),
+    (
+        "Outbound downlink stats",
+        |s| s.stats_outbound_downlink,
+        |s, v| s.stats_outbound_downlink = Some(v),
+    ),
+];
+
+/// 获取 core 的 policy
+///
+/// ## Arguments
+///
+/// * `server` - 服务器地址
+async fn get_policy(server: &str) -> Result<BaseResponse<Policy>, String> {
+    let address = format!("{}{}", server, RequestApi::Policy);
+    let resquest = axios(&address, Method::GET).send().await;
+    match resquest {
+        Ok(response) => response.json().await.map_err(error_to_string),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// 保存 core 的 policy 并重启 core
+///
+/// ## Arguments
+///
+/// * `server` - 服务器地址
+/// * `policy` - 新的 policy
+async fn save_policy(server: &str, policy: &Policy) -> Result<BaseResponse<Policy>, String> {
+    let address = format!("{}{}", server, RequestApi::Policy);
+    let resquest = axios(&address, Method::PUT)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(policy).map_err(error_to_string)?)
+        .map_err(error_to_string)?
+        .send()
+        .await;
+    match resquest {
+        Ok(response) => response.json().await.map_err(error_to_string),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Timeouts and buffer sizes per user level
+#[component]
+pub fn PolicyForm() -> impl IntoView {
+    let user = use_global_user();
+    let toaster = ToasterInjection::expect_context();
+
+    let policy = RwSignal::new(Policy::default());
+    let (saving, set_saving) = signal(false);
+
+    let server = user.get_untracked().server;
+    spawn_local(async move {
+        match get_policy(&server).await {
+            Ok(BaseResponse {
+                data: Some(data), ..
+            }) => policy.set(data),
+            Ok(response) => dispatch_toast(
+                toaster,
+                ToastIntent::Error,
+                "Policy".into(),
+                format!("Load policy failed: {}", response.message),
+            ),
+            Err(err) => logging::error!("load policy failed {err}"),
+        }
+    });
+
+    let add_level = move |_| {
+        policy.update(|p| {
+            let next = p
+                .levels
+                .keys()
+                .filter_map(|level| level.parse::<u32>().ok())
+                .max()
+                .map_or(0, |level| level + 1);
+            p.levels.insert(next.to_string(), Levels::default());
+        })
+    };
+
+    let save = move |_| {
+        if saving.get_untracked() {
+            return;
+        }
+        let server = user.get_untracked().server;
+        let data = policy.get_untracked();
+        set_saving(true);
+        spawn_local(async move {
+            match save_policy(&server, &data).await {
+                Ok(response) if response.code == 200 => dispatch_toast(
+                    toaster,
+                    ToastIntent::Success,
+                    "Policy".into(),
+                    "Policy saved".into(),
+                ),
+                Ok(response) => dispatch_toast(
+                    toaster,
+                    ToastIntent::Error,
+                    "Policy".into(),
+                    response.message,
+                ),
+                Err(err) => dispatch_toast(toaster, ToastIntent::Error, "Policy".into(), err),
+            }
+            set_saving(false);
+        });
+    };
+
+    let level_row = move |level: String| {
+        let fields = LEVEL_FIELDS
+            .iter()
+            .map(|field| {
+                let (level, get, set) = (level.clone(), field.get, field.set);
+                let value = {
+                    let level = level.clone();
+                    move || {
+                        policy.with(|p| {
+                            p.levels
+                                .get(&level)
+                                .and_then(get)
+                                .map(|v| v.to_string())
+                                .unwrap_or_default()
+                        })
+                    }
+                };
+                let on_input = move |ev: ev::Event| {
+                    // an empty field uses the core default
+                    let value = event_target_value(&ev).trim().parse::<i64>().ok();
+                    policy.update(|p| {
+                        if let Some(levels) = p.levels.get_mut(&level) {
+                            set(levels, value);
+                        }
+                    });
+                };
+                view! {
+                    <label class="input input-bordered input-sm flex items-center gap-2">
+                        <span class="text-gray-400">{field.label}</span>
+                        <input
+                            type="number"
+                            min="0"
+                            class="w-16 grow"
+                            placeholder="default"
+                            prop:value=value
+                            on:input=on_input
+                        />
+                        <span class="text-gray-400">{field.unit}</span>
+                    </label>
+                }
+            })
+            .collect_view();
+        let remove = {
+            let level = level.clone();
+            move |_| {
+                policy.update(|p| {
+                    p.levels.remove(&level);
+                })
+            }
+        };
+        view! {
+            <div class="flex flex-wrap items-center gap-2 py-2">
+                <span class="w-16 font-bold">{format!("Level {level}")}</span>
+                {fields}
+                <button class="btn btn-sm btn-ghost" on:click=remove>
+                    Remove
+                </button>
+            </div>
+        }
+    };
+
+    let system_fields = SYSTEM_FIELDS
+        .iter()
+        .map(|(label, get, set)| {
+            let (get, set) = (*get, *set);
+            view! {
+                <label class="label cursor-pointer gap-2">
+                    <span class="label-text">{*label}</span>
+                    <input
+                        type="checkbox"
+                        class="toggle toggle-sm"
+                        prop:checked=move || policy.with(|p| get(&p.system).unwrap_or_default())
+                        on:change=move |ev| {
+                            let checked = event_target_checked(&ev);
+                            policy.update(|p| set(&mut p.system, checked));
+                        }
+                    />
+                </label>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <div class="mt-4 p-4 rounded-lg bg-stone-50 dark:bg-rua-gray-800">
+            <div class="flex flex-wrap items-center gap-2 pb-2">
+                <span class="text-lg font-bold">Policy</span>
+                <div class="flex-1"></div>
+                <button class="btn btn-sm" on:click=add_level disabled=saving>
+                    Add level
+                </button>
+                <button class="btn btn-sm btn-primary" on:click=save disabled=saving>
+                    Save & Apply
+                </button>
+            </div>
+
+            <For
+                each=move || policy.with(|p| p.levels.keys().cloned().collect::<Vec<_>>())
+                key=|level| level.clone()
+                children=level_row
+            />
+
+            <div class="flex flex-wrap gap-4 pt-2">{system_fields}</div>
+        </div>
+    }
+}
@@ -0,0 +1,280 @@
+use gloo::net::http::Method;
+use leptos::{ev, logging, prelude::*, task::spawn_local};
+use serde::{Deserialize, Serialize};
+use thaw::{ToastIntent, ToasterInjection};
+
+use crate::{
+    api::{axios, BaseResponse, RequestApi},
+    hooks::{dispatch_toast, use_global_user},
+    utils::error_to_string,
+};
+
+/// System-wide proxying through the core's TUN stack
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TunSetting {
+    pub enabled: bool,
+    pub name: String,
+    pub mtu: u32,
+    /// Addresses of the stack, as CIDRs
+    pub ips: Vec<String>,
+    /// Destinations routed into the device, as CIDRs
+    pub routes: Vec<String>,
+    pub sniffing: bool,
+    /// SO_MARK set on every outbound
+    pub mark: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct TunPayload<'a> {
+    setting: &'a TunSetting,
+}
+
+/// 获取 core 的 TUN 设置
+///
+/// ## Arguments
+///
+/// * `server` - 服务器地址
+async fn get_tun(server: &str) -> Result<BaseResponse<TunSetting>, String> {
+    let address = format!("{}{}", server, RequestApi::Tun);
+    let resquest = axios(&address, Method::GET).send().await;
+    match resquest {
+        Ok(response) => response.json().await.map_err(error_to_string),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// 保存 TUN 设置并重启 core
+///
+/// ## Arguments
+///
+/// * `server` - 服务器地址
+/// * `setting` - 新的 TUN 设置
+async fn save_tun(server: &str, setting: &TunSetting) -> Result<BaseResponse<TunSetting>, String> {
+    let address = format!("{}{}", server, RequestApi::Tun);
+    let body = serde_json::to_string(&TunPayload { setting }).map_err(error_to_string)?;
+    let resquest = axios(&address, Method::PUT)
+        .header("Content-Type", "application/json")
+        .body(body)
+        .map_err(error_to_string)?
+        .send()
+        .await;
+    match resquest {
+        Ok(response) => response.json().await.map_err(error_to_string),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// 获取当前 TUN 设置的路由脚本
+///
+/// ## Arguments
+///
+/// * `server` - 服务器地址
+async fn get_tun_script(server: &str) -> Result<BaseResponse<String>, String> {
+    let address = format!("{}{}", server, RequestApi::TunScript);
+    let resquest = axios(&address, Method::GET).send().await;
+    match resquest {
+        Ok(response) => response.json().await.map_err(error_to_string),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Comma separated CIDRs, empty items are dropped
+fn split_cidrs(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|cidr| !cidr.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// TUN device and the routes sent into it
+///
+/// System routes are not touched, the script is shown to run by hand.
+#[component]
+pub fn TunForm() -> impl IntoView {
+    let user = use_global_user();
+    let toaster = ToasterInjection::expect_context();
+
+    let tun = RwSignal::new(TunSetting::default());
+    let script = RwSignal::new(None::<String>);
+    let (saving, set_saving) = signal(false);
+
+    let server = user.get_untracked().server;
+    spawn_local(async move {
+        match get_tun(&server).await {
+            Ok(BaseResponse {
+                data: Some(data), ..
+            }) => tun.set(data),
+            Ok(response) => dispatch_toast(
+                toaster,
+                ToastIntent::Error,
+                "TUN".into(),
+                format!("Load TUN setting failed: {}", response.message),
+            ),
+            Err(err) => logging::error!("load tun failed {err}"),
+        }
+    });
+
+    let save = move |_| {
+        if saving.get_untracked() {
+            return;
+        }
+        let server = user.get_untracked().server;
+        let data = tun.get_untracked();
+        set_saving(true);
+        spawn_local(async move {
+            match save_tun(&server, &data).await {
+                Ok(response) if response.code == 200 => {
+                    // the script of the previous setting is stale
+                    script.set(None);
+                    dispatch_toast(
+                        toaster,
+                        ToastIntent::Success,
+                        "TUN".into(),
+                        "TUN setting saved".into(),
+                    )
+                }
+                Ok(response) => {
+                    dispatch_toast(toaster, ToastIntent::Error, "TUN".into(), response.message)
+                }
+                Err(err) => dispatch_toast(toaster, ToastIntent::Error, "TUN".into(), err),
+            }
+            set_saving(false);
+        });
+    };
+
+    let show_script = move |_| {
+        let server = user.get_untracked().server;
+        spawn_local(async move {
+            match get_tun_script(&server).await {
+                Ok(BaseResponse {
+                    data: Some(data), ..
+                }) => script.set(Some(data)),
+                Ok(response) => {
+                    dispatch_toast(toaster, ToastIntent::Error, "TUN".into(), response.message)
+                }
+                Err(err) => dispatch_toast(toaster, ToastIntent::Error, "TUN".into(), err),
+            }
+        });
+    };
+
+    view! {
+        <div class="mt-4 p-4 rounded-lg bg-stone-50 dark:bg-rua-gray-800">
+            <div class="flex flex-wrap items-center gap-2 pb-2">
+                <span class="text-lg font-bold">TUN</span>
+                <label class="label cursor-pointer gap-2">
+                    <input
+                        type="checkbox"
+                        class="toggle toggle-sm"
+                        prop:checked=move || tun.with(|t| t.enabled)
+                        on:change=move |ev| {
+                            let checked = event_target_checked(&ev);
+                            tun.update(|t| t.enabled = checked);
+                        }
+                    />
+                </label>
+                <div class="flex-1"></div>
+                <button class="btn btn-sm" on:click=show_script>
+                    Routing script
+                </button>
+                <button class="btn btn-sm btn-primary" on:click=save disabled=saving>
+                    Save & Apply
+                </button>
+            </div>
+
+            <div class="flex flex-wrap items-center gap-2 py-2">
+                <label class="input input-bordered input-sm flex items-center gap-2">
+                    <span class="text-gray-400">Device</span>
+                    <input
+                        type="text"
+                        class="w-24 grow"
+                        maxlength="15"
+                        prop:value=move || tun.with(|t| t.name.clone())
+                        on:input=move |ev: ev::Event| {
+                            let value = event_target_value(&ev);
+                            tun.update(|t| t.name = value.trim().to_string());
+                        }
+                    />
+                </label>
+                <label class="input input-bordered input-sm flex items-center gap-2">
+                    <span class="text-gray-400">MTU</span>
+                    <input
+                        type="number"
+                        min="576"
+                        max="65535"
+                        class="w-20 grow"
+                        prop:value=move || tun.with(|t| t.mtu.to_string())
+                        on:input=move |ev: ev::Event| {
+                            if let Ok(mtu) = event_target_value(&ev).trim().parse::<u32>() {
+                                tun.update(|t| t.mtu = mtu);
+                            }
+                        }
+                    />
+                </label>
+                <label class="input input-bordered input-sm flex items-center gap-2">
+                    <span class="text-gray-400">Mark</span>
+                    <input
+                        type="number"
+                        min="1"
+                        class="w-20 grow"
+                        prop:value=move || tun.with(|t| t.mark.to_string())
+                        on:input=move |ev: ev::Event| {
+                            if let Ok(mark) = event_target_value(&ev).trim().parse::<u32>() {
+                                tun.update(|t| t.mark = mark);
+                            }
+                        }
+                    />
+                </label>
+                <label class="label cursor-pointer gap-2">
+                    <span class="label-text">Sniffing</span>
+                    <input
+                        type="checkbox"
+                        class="toggle toggle-sm"
+                        prop:checked=move || tun.with(|t| t.sniffing)
+                        on:change=move |ev| {
+                            let checked = event_target_checked(&ev);
+                            tun.update(|t| t.sniffing = checked);
+                        }
+                    />
+                </label>
+            </div>
+
+            <div class="flex flex-wrap items-center gap-2 py-2">
+                <label class="input input-bordered input-sm flex flex-1 items-center gap-2">
+                    <span class="text-gray-400">Addresses</span>
+                    <input
+                        type="text"
+                        class="grow"
+                        placeholder="172.19.0.1/30, fdfe:dcba:9876::1/126"
+                        prop:value=move || tun.with(|t| t.ips.join(", "))
+                        on:change=move |ev| {
+                            let value = event_target_value(&ev);
+                            tun.update(|t| t.ips = split_cidrs(&value));
+                        }
+                    />
+                </label>
+                <label class="input input-bordered input-sm flex flex-1 items-center gap-2">
+                    <span class="text-gray-400">Routes</span>
+                    <input
+                        type="text"
+                        class="grow"
+                        placeholder="0.0.0.0/0, ::/0"
+                        prop:value=move || tun.with(|t| t.routes.join(", "))
+                        on:change=move |ev| {
+                            let value = event_target_value(&ev);
+                            tun.update(|t| t.routes = split_cidrs(&value));
+                        }
+                    />
+                </label>
+            </div>
+
+            <Show when=move || script.with(Option::is_some)>
+                <pre class="mt-2 p-2 overflow-x-auto rounded text-sm bg-stone-100 dark:bg-rua-gray-900">
+                    {move || script.get().unwrap_or_default()}
+                </pre>
+            </Show>
+        </div>
+    }
+}
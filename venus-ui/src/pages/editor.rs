@@ -1,12 +1,323 @@
-use leptos::prelude::*;
+use gloo::net::http::Method;
+use leptos::{html, logging, prelude::*, task::spawn_local};
+use leptos_use::signal_debounced;
+use serde::{Deserialize, Serialize};
+use thaw::{ToastIntent, ToasterInjection};
 
-use crate::components::title::Title;
+use crate::{
+    api::{axios, BaseResponse, RequestApi},
+    components::title::Title,
+    hooks::{dispatch_toast, use_global_user},
+    utils::error_to_string,
+};
+
+/// Wait after the last keystroke before validating
+const VALIDATE_DELAY: f64 = 1000.0;
+
+/// Raw core config, `restart` is only used when saving
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RawCore {
+    pub content: String,
+    #[serde(default)]
+    pub restart: bool,
+}
+
+/// A problem found in the core config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigIssue {
+    /// `parse` or `core`
+    pub source: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// 获取 core 配置文件原文
+///
+/// ## Arguments
+///
+/// * `server` - 服务器地址
+async fn get_raw(server: &str) -> Result<BaseResponse<RawCore>, String> {
+    let address = format!("{}{}", server, RequestApi::CoreRaw);
+    let resquest = axios(&address, Method::GET).send().await;
+    match resquest {
+        Ok(response) => response.json().await.map_err(error_to_string),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// 保存 core 配置文件原文
+///
+/// ## Arguments
+///
+/// * `server` - 服务器地址
+/// * `raw` - 配置原文，`restart` 为 true 时重启 core
+async fn save_raw(server: &str, raw: &RawCore) -> Result<BaseResponse<()>, String> {
+    let address = format!("{}{}", server, RequestApi::CoreRaw);
+    let resquest = axios(&address, Method::PUT)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(raw).map_err(error_to_string)?)
+        .map_err(error_to_string)?
+        .send()
+        .await;
+    match resquest {
+        Ok(response) => response.json().await.map_err(error_to_string),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// 校验 core 配置文件原文，不保存
+///
+/// ## Arguments
+///
+/// * `server` - 服务器地址
+/// * `content` - 配置原文
+async fn validate_raw(
+    server: &str,
+    content: String,
+) -> Result<BaseResponse<Vec<ConfigIssue>>, String> {
+    let address = format!("{}{}", server, RequestApi::CoreRawValidate);
+    let raw = RawCore {
+        content,
+        restart: false,
+    };
+    let resquest = axios(&address, Method::POST)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&raw).map_err(error_to_string)?)
+        .map_err(error_to_string)?
+        .send()
+        .await;
+    match resquest {
+        Ok(response) => response.json().await.map_err(error_to_string),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// UTF-16 offset of a 1-based line and byte column in `content`, as used by
+/// `set_selection_range`
+fn offset_of(content: &str, line: usize, column: usize) -> usize {
+    let line_start = content
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    let mut end = (line_start + column.saturating_sub(1)).min(content.len());
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    content[..end].encode_utf16().count()
+}
 
 #[component]
 pub fn Editor() -> impl IntoView {
+    let user = use_global_user();
+    let toaster = ToasterInjection::expect_context();
+
+    let content = RwSignal::new(String::new());
+    // content as loaded or last saved
+    let saved = RwSignal::new(String::new());
+    let issues = RwSignal::new(Vec::<ConfigIssue>::new());
+    let (validating, set_validating) = signal(false);
+    let (saving, set_saving) = signal(false);
+    let dirty = move || content.with(|c| saved.with(|s| c != s));
+
+    let load = move || {
+        let server = user.get_untracked().server;
+        spawn_local(async move {
+            match get_raw(&server).await {
+                Ok(BaseResponse {
+                    data: Some(raw), ..
+                }) => {
+                    saved.set(raw.content.clone());
+                    content.set(raw.content);
+                    issues.set(vec![]);
+                }
+                Ok(response) => {
+                    dispatch_toast(
+                        toaster,
+                        ToastIntent::Error,
+                        "Editor".into(),
+                        format!("Load config failed: {}", response.message),
+                    );
+                }
+                Err(err) => logging::error!("load core config failed {err}"),
+            }
+        });
+    };
+    load();
+
+    // validate after typing stops
+    let debounced = signal_debounced(content, VALIDATE_DELAY);
+    Effect::new(move |_| {
+        let content = debounced.get();
+        if content.is_empty() || content == saved.get_untracked() {
+            return;
+        }
+        let server = user.get_untracked().server;
+        set_validating(true);
+        spawn_local(async move {
+            match validate_raw(&server, content).await {
+                Ok(response) => issues.set(response.data.unwrap_or_default()),
+                Err(err) => logging::error!("validate core config failed {err}"),
+            }
+            set_validating(false);
+        });
+    });
+
+    let save = move |restart: bool| {
+        if saving.get_untracked() {
+            return;
+        }
+        let server = user.get_untracked().server;
+        let raw = RawCore {
+            content: content.get_untracked(),
+            restart,
+        };
+        set_saving(true);
+        spawn_local(async move {
+            match save_raw(&server, &raw).await {
+                Ok(response) if response.code == 200 => {
+                    saved.set(raw.content);
+                    issues.set(vec![]);
+                    let body = if restart {
+                        "Config saved, core restarted"
+                    } else {
+                        "Config saved"
+                    };
+                    dispatch_toast(toaster, ToastIntent::Success, "Editor".into(), body.into());
+                }
+                Ok(response) => dispatch_toast(
+                    toaster,
+                    ToastIntent::Error,
+                    "Editor".into(),
+                    response.message,
+                ),
+                Err(err) => {
+                    dispatch_toast(toaster, ToastIntent::Error, "Editor".into(), err);
+                }
+            }
+            set_saving(false);
+        });
+    };
+
+    // line numbers follow the textarea scroll
+    let textarea: NodeRef<html::Textarea> = NodeRef::new();
+    let gutter: NodeRef<html::Div> = NodeRef::new();
+    let sync_scroll = move |_| {
+        if let (Some(textarea), Some(gutter)) = (textarea.get(), gutter.get()) {
+            gutter.set_scroll_top(textarea.scroll_top());
+        }
+    };
+    let line_count = move || content.with(|c| c.lines().count().max(1));
+    let issue_lines = move || {
+        issues.with(|issues| {
+            issues
+                .iter()
+                .filter_map(|issue| issue.line)
+                .collect::<Vec<_>>()
+        })
+    };
+
+    // move the caret to an issue
+    let jump_to = move |line: usize, column: usize| {
+        let Some(textarea) = textarea.get() else {
+            return;
+        };
+        let offset = content.with_untracked(|c| offset_of(c, line, column)) as u32;
+        textarea.focus().ok();
+        textarea.set_selection_range(offset, offset).ok();
+    };
+
     view! {
-        <div>
+        <div class="flex flex-col h-full">
             <Title>Editor</Title>
+
+            <div class="flex flex-wrap items-center gap-2 pb-4">
+                <span class="text-sm text-gray-400">config.json</span>
+                <Show when=dirty>
+                    <span class="badge badge-sm badge-warning">modified</span>
+                </Show>
+                <Show when=move || validating.get()>
+                    <span class="loading loading-spinner loading-xs"></span>
+                </Show>
+                <div class="flex-1"></div>
+                <button class="btn btn-sm" on:click=move |_| load() disabled=saving>
+                    Reload
+                </button>
+                <button
+                    class="btn btn-sm"
+                    on:click=move |_| save(false)
+                    disabled=move || saving.get() || !dirty()
+                >
+                    Save
+                </button>
+                <button
+                    class="btn btn-sm btn-primary"
+                    on:click=move |_| save(true)
+                    disabled=move || saving.get() || !issues.with(Vec::is_empty)
+                >
+                    Save & Apply
+                </button>
+            </div>
+
+            <div class="flex flex-1 min-h-0 rounded-lg overflow-hidden font-mono text-sm bg-stone-50 dark:bg-rua-gray-800">
+                <div
+                    node_ref=gutter
+                    class="py-4 px-2 overflow-hidden text-right text-gray-400 select-none"
+                >
+                    {move || {
+                        let marked = issue_lines();
+                        (1..=line_count())
+                            .map(|line| {
+                                let class = if marked.contains(&line) {
+                                    "leading-5 text-error font-bold"
+                                } else {
+                                    "leading-5"
+                                };
+                                view! { <div class=class>{line}</div> }
+                            })
+                            .collect_view()
+                    }}
+                </div>
+                <textarea
+                    node_ref=textarea
+                    class="flex-1 py-4 px-2 leading-5 bg-transparent outline-none resize-none whitespace-pre overflow-auto"
+                    spellcheck="false"
+                    prop:value=content
+                    on:input=move |ev| content.set(event_target_value(&ev))
+                    on:scroll=sync_scroll
+                ></textarea>
+            </div>
+
+            <Show when=move || !issues.with(Vec::is_empty)>
+                <ul class="mt-4 p-4 rounded-lg text-sm bg-stone-50 dark:bg-rua-gray-800">
+                    <For
+                        each=move || issues.get()
+                        key=|issue| (issue.line, issue.column, issue.message.clone())
+                        children=move |issue| {
+                            let position = match (issue.line, issue.column) {
+                                (Some(line), Some(column)) => format!("{line}:{column}"),
+                                _ => issue.source.clone(),
+                            };
+                            view! {
+                                <li
+                                    class="flex gap-2 py-0.5 cursor-pointer text-error"
+                                    on:click=move |_| {
+                                        if let (Some(line), Some(column)) = (issue.line, issue.column) {
+                                            jump_to(line, column);
+                                        }
+                                    }
+                                >
+                                    <span class="shrink-0 font-mono">{position}</span>
+                                    <span class="break-all">{issue.message.clone()}</span>
+                                </li>
+                            }
+                        }
+                    />
+                </ul>
+            </Show>
         </div>
     }
 }
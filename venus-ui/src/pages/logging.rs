@@ -1,12 +1,285 @@
-use leptos::prelude::*;
+use std::collections::VecDeque;
 
-use crate::components::title::Title;
+use futures::StreamExt;
+use gloo::net::{eventsource::futures::EventSource, http::Method};
+use leptos::{html, logging, prelude::*, task::spawn_local};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{axios, BaseResponse, RequestApi},
+    components::title::Title,
+    hooks::use_global_user,
+    utils::error_to_string,
+    User,
+};
+
+/// Max entries kept on the page, older ones are dropped
+const MAX_LOG_ENTRIES: usize = 1000;
+/// Entries loaded before streaming, the server caps a page at 500
+const LOG_HISTORY_SIZE: usize = 500;
+/// Levels of the filter select, in ascending order
+const LOG_LEVELS: [&str; 4] = ["debug", "info", "warning", "error"];
+
+/// Log entry from venus server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub id: u64,
+    /// RFC 3339 time
+    pub timestamp: String,
+    pub level: String,
+    /// `core` or `venus`
+    pub source: String,
+    pub component: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    pub total: usize,
+    /// Newest first
+    pub entries: Vec<LogEntry>,
+}
+
+/// 获取最近的日志
+///
+/// ## Arguments
+///
+/// * `server` - 服务器地址
+/// * `level` - 最低日志等级
+async fn get_logs(server: &str, level: &str) -> Result<BaseResponse<LogPage>, String> {
+    let address = format!(
+        "{}{}?level={}&size={}",
+        server,
+        RequestApi::Logs,
+        level,
+        LOG_HISTORY_SIZE
+    );
+    let resquest = axios(&address, Method::GET).send().await;
+    match resquest {
+        Ok(response) => response.json().await.map_err(error_to_string),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Tailwind class of the level badge
+fn level_class(level: &str) -> &'static str {
+    match level {
+        "error" => "badge-error",
+        "warning" => "badge-warning",
+        "info" => "badge-info",
+        _ => "badge-ghost",
+    }
+}
+
+/// Append entries and drop the oldest ones over `MAX_LOG_ENTRIES`
+fn append_entries(entries: &mut VecDeque<LogEntry>, new: impl IntoIterator<Item = LogEntry>) {
+    entries.extend(new);
+    while entries.len() > MAX_LOG_ENTRIES {
+        entries.pop_front();
+    }
+}
 
 #[component]
 pub fn Logging() -> impl IntoView {
+    let user = use_global_user();
+
+    let entries = RwSignal::new(VecDeque::<LogEntry>::new());
+    // entries received while paused
+    let pending = StoredValue::new(Vec::<LogEntry>::new());
+    let (level, set_level) = signal("info".to_string());
+    let (search, set_search) = signal(String::new());
+    let (follow, set_follow) = signal(true);
+    let (paused, set_paused) = signal(false);
+
+    // every (re)connect bumps the generation, stale streams stop on their next event
+    let generation = StoredValue::new(0_u32);
+    let source = StoredValue::new_local(None::<EventSource>);
+    let close_source = move || {
+        source.update_value(|source| {
+            if let Some(source) = source.take() {
+                source.close();
+            }
+        });
+    };
+
+    Effect::new(move |_| {
+        let level = level.get();
+        let User { server, token, .. } = user.get();
+        close_source();
+        generation.update_value(|g| *g += 1);
+        let current = generation.get_value();
+
+        spawn_local(async move {
+            let history = match get_logs(&server, &level).await {
+                Ok(res) => res.data.unwrap_or_default().entries,
+                Err(err) => {
+                    logging::error!("get logs failed {err}");
+                    vec![]
+                }
+            };
+            let mut history_entries = VecDeque::new();
+            append_entries(&mut history_entries, history.into_iter().rev());
+            entries.set(history_entries);
+            pending.set_value(vec![]);
+
+            let url = format!(
+                "{}{}?token={}&level={}",
+                server,
+                RequestApi::LogsStream,
+                token,
+                level
+            );
+            let mut event_source = match EventSource::new(&url) {
+                Ok(event_source) => event_source,
+                Err(err) => {
+                    logging::error!("connect log stream failed {err:?}");
+                    return;
+                }
+            };
+            let mut stream = match event_source.subscribe("log") {
+                Ok(stream) => stream,
+                Err(err) => {
+                    logging::error!("subscribe log stream failed {err:?}");
+                    return;
+                }
+            };
+            source.set_value(Some(event_source));
+
+            while let Some(Ok((_, event))) = stream.next().await {
+                if generation.try_with_value(|g| *g == current) != Some(true) {
+                    break;
+                }
+                let Some(entry) = event
+                    .data()
+                    .as_string()
+                    .and_then(|data| serde_json::from_str::<LogEntry>(&data).ok())
+                else {
+                    continue;
+                };
+                if paused.get_untracked() {
+                    pending.update_value(|pending| pending.push(entry));
+                } else {
+                    entries.update(|entries| append_entries(entries, [entry]));
+                }
+            }
+        });
+    });
+    on_cleanup(move || {
+        generation.update_value(|g| *g += 1);
+        close_source();
+    });
+
+    let toggle_pause = move |_| {
+        if paused.get_untracked() {
+            let flushed = pending.try_update_value(std::mem::take).unwrap_or_default();
+            entries.update(|entries| append_entries(entries, flushed));
+        }
+        set_paused.update(|paused| *paused = !*paused);
+    };
+
+    let filtered = move || {
+        let keyword = search.get().to_lowercase();
+        entries
+            .get()
+            .into_iter()
+            .filter(|entry| {
+                keyword.is_empty()
+                    || entry.message.to_lowercase().contains(&keyword)
+                    || entry
+                        .component
+                        .as_ref()
+                        .is_some_and(|c| c.to_lowercase().contains(&keyword))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // follow mode keeps the newest entry in view
+    let container: NodeRef<html::Div> = NodeRef::new();
+    Effect::new(move |_| {
+        entries.track();
+        search.track();
+        if !follow.get() {
+            return;
+        }
+        if let Some(container) = container.get() {
+            container.set_scroll_top(container.scroll_height());
+        }
+    });
+
     view! {
-        <div>
+        <div class="flex flex-col h-full">
             <Title>Logging</Title>
+
+            <div class="flex flex-wrap items-center gap-2 pb-4">
+                <select
+                    class="select select-bordered select-sm"
+                    on:change=move |ev| set_level(event_target_value(&ev))
+                >
+                    {LOG_LEVELS
+                        .map(|l| {
+                            view! {
+                                <option value=l selected=move || level.get() == l>
+                                    {l}
+                                </option>
+                            }
+                        })}
+                </select>
+                <label class="input input-bordered input-sm flex items-center gap-2">
+                    <span class="icon-[solar--magnifer-linear]"></span>
+                    <input
+                        type="text"
+                        class="grow"
+                        placeholder="Search"
+                        prop:value=search
+                        on:input=move |ev| set_search(event_target_value(&ev))
+                    />
+                </label>
+                <label class="label cursor-pointer gap-2">
+                    <span class="label-text">Follow</span>
+                    <input
+                        type="checkbox"
+                        class="toggle toggle-sm"
+                        prop:checked=follow
+                        on:change=move |ev| set_follow(event_target_checked(&ev))
+                    />
+                </label>
+                <button class="btn btn-sm" on:click=toggle_pause>
+                    {move || if paused.get() { "Resume" } else { "Pause" }}
+                </button>
+                <button class="btn btn-sm" on:click=move |_| entries.update(|e| e.clear())>
+                    Clear
+                </button>
+            </div>
+
+            <div
+                node_ref=container
+                class="flex-1 overflow-auto p-4 rounded-lg font-mono text-sm bg-stone-50 dark:bg-rua-gray-800"
+            >
+                <For
+                    each=filtered
+                    key=|entry| entry.id
+                    children=move |entry| {
+                        let time = entry.timestamp.get(11..19).unwrap_or_default().to_string();
+                        view! {
+                            <div class="flex gap-2 py-0.5">
+                                <span class="text-gray-400 shrink-0">{time}</span>
+                                <span class=format!(
+                                    "badge badge-sm shrink-0 {}",
+                                    level_class(&entry.level),
+                                )>{entry.level.clone()}</span>
+                                <span class="text-gray-400 shrink-0">{entry.source.clone()}</span>
+                                {entry
+                                    .component
+                                    .clone()
+                                    .map(|c| view! { <span class="text-gray-500 shrink-0">{c}</span> })}
+                                <span class="break-all">{entry.message.clone()}</span>
+                            </div>
+                        }
+                    }
+                />
+            </div>
         </div>
     }
 }
@@ -1,6 +1,10 @@
 use leptos::prelude::*;
 
-use crate::components::{dark_mode_btn::DarkMode, title::Title};
+use crate::components::{
+    dark_mode_btn::DarkMode,
+    settings_page::{policy::PolicyForm, tun::TunForm},
+    title::Title,
+};
 
 #[component]
 pub fn Settings() -> impl IntoView {
@@ -11,6 +15,10 @@ pub fn Settings() -> impl IntoView {
             <div>
                 <DarkMode />
             </div>
+
+            <PolicyForm />
+
+            <TunForm />
         </div>
     }
 }
@@ -17,3 +17,13 @@ pub static VENUS_UI_PATH: LazyLock<Cow<'static, str>> = LazyLock::new(|| {
         })
         .unwrap_or(DEFAULT_VENUS_UI_PATH.into()).into()
 });
+
+/// Default count of log entries kept in memory
+pub const DEFAULT_VENUS_LOG_CAPACITY: usize = 1000;
+/// Count of log entries kept in memory, read from environment varable `VENUS_LOG_CAPACITY`
+pub static VENUS_LOG_CAPACITY: LazyLock<usize> = LazyLock::new(|| {
+    env::var("VENUS_LOG_CAPACITY")
+        .ok()
+        .and_then(|capacity| capacity.parse().ok())
+        .unwrap_or(DEFAULT_VENUS_LOG_CAPACITY)
+});
@@ -1,23 +1,32 @@
-use std::{
-    process::exit,
-    sync::mpsc::{self},
+use std::process::exit;
+use tokio::{
+    sync::{broadcast::Sender, mpsc, Mutex, OnceCell},
+    time,
 };
-use tokio::sync::{Mutex, OnceCell};
-use tracing::error;
+use tracing::{error, info, warn};
 
-use venus_core::{message::Message, Venus};
+use venus_core::{
+    config::watcher::{watch_config, SyncStatus},
+    consts::CONFIG_WATCH_DEBOUNCE,
+    error::log_err,
+    message::{self, MessageType},
+    Venus,
+};
+
+/// History author of config edits made outside venus
+const EXTERNAL_AUTHOR: &str = "external";
 
-static MSG: OnceCell<Mutex<Message>> = OnceCell::const_new();
-pub async fn global_message() -> &'static Mutex<Message> {
-    MSG.get_or_init(|| async { Mutex::new(mpsc::channel()) })
-        .await
+static MSG: OnceCell<Sender<MessageType>> = OnceCell::const_new();
+/// Core message channel, call `subscribe` to receive core output
+pub async fn global_message() -> &'static Sender<MessageType> {
+    MSG.get_or_init(|| async { message::channel() }).await
 }
 
 static CORE: OnceCell<Mutex<Venus>> = OnceCell::const_new();
 pub async fn global_core() -> &'static Mutex<Venus> {
     CORE.get_or_init(|| async {
-        let msg = global_message().await.lock().await;
-        match Venus::new(msg.0.clone()) {
+        let msg = global_message().await;
+        match Venus::new(msg.clone()) {
             Ok(v) => Mutex::new(v),
             Err(err) => {
                 error!("cannot initialize venus core {err}");
@@ -27,3 +36,47 @@ pub async fn global_core() -> &'static Mutex<Venus> {
     })
     .await
 }
+
+/// Reload config files edited outside venus
+///
+/// Venus's own writes are reported by the watcher as well,
+/// `reload_external` skips them as synced. A reloaded edit is recorded
+/// in the history by `external`.
+pub fn spawn_config_watcher() -> anyhow::Result<()> {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let watcher = watch_config(tx)?;
+    tokio::spawn(async move {
+        // the watcher stops when dropped
+        let _watcher = watcher;
+        while let Some(kind) = rx.recv().await {
+            time::sleep(CONFIG_WATCH_DEBOUNCE).await;
+            let mut kinds = vec![kind];
+            while let Ok(kind) = rx.try_recv() {
+                if !kinds.contains(&kind) {
+                    kinds.push(kind);
+                }
+            }
+
+            let venus = &mut global_core().await.lock().await;
+            for kind in kinds {
+                match venus.reload_external(kind, false).await {
+                    Ok(SyncStatus::Modified) => {
+                        info!("{kind:?} config edited outside venus, reloaded");
+                        let reason = format!("{kind:?} config edited outside venus").to_lowercase();
+                        venus
+                            .config
+                            .record_history(EXTERNAL_AUTHOR, &reason)
+                            .map_err(log_err)
+                            .ok();
+                    }
+                    Ok(SyncStatus::Conflict) => warn!(
+                        "{kind:?} config edited outside venus conflicts with unsaved changes, not reloaded"
+                    ),
+                    Ok(SyncStatus::Synced) => {}
+                    Err(err) => error!("reloading {kind:?} config failed {err}"),
+                }
+            }
+        }
+    });
+    Ok(())
+}
@@ -9,7 +9,10 @@ use axum::{
 use serde_json::json;
 use serde_repr::*;
 use tracing::error;
-use venus_core::error::{self, VenusError};
+use venus_core::{
+    config::error::ConfigError,
+    error::{self, VenusError},
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum RouteError {
@@ -62,6 +65,7 @@ pub enum ErrorCode {
     AuthorizeFailed = 1002,
     UserConflict = 1003,
     ParameterIncorrect = 1004,
+    ConfigConflict = 1005,
 }
 
 impl Display for ErrorCode {
@@ -75,6 +79,7 @@ impl Display for ErrorCode {
             AuthorizeFailed => "用户名或密码错误",
             UserConflict => "该用户已经存在",
             ParameterIncorrect => "请求参数错误",
+            ConfigConflict => "配置文件已被外部修改",
         };
         f.write_str(res)?;
         Ok(())
@@ -93,6 +98,43 @@ fn log_internal_error<T: Display>(err: T) -> (StatusCode, ErrorCode, String) {
     )
 }
 
+/// Map a config error to its response, shared by the core and config arms
+fn config_error_response(err: ConfigError) -> (StatusCode, ErrorCode, String) {
+    use ErrorCode::*;
+
+    match err {
+        ConfigError::BackupNotFound(name) => (
+            StatusCode::BAD_REQUEST,
+            ParameterIncorrect,
+            format!("Backup {} not found", name),
+        ),
+        ConfigError::SnapshotNotFound(id) => (
+            StatusCode::BAD_REQUEST,
+            ParameterIncorrect,
+            format!("Snapshot {} not found", id),
+        ),
+        ConfigError::Conflict(path) => (
+            StatusCode::CONFLICT,
+            ConfigConflict,
+            format!("{} was edited outside venus", path.display()),
+        ),
+        err @ ConfigError::InvalidCore { .. } => (
+            StatusCode::BAD_REQUEST,
+            ParameterIncorrect,
+            format!("Invalid core config {}", err),
+        ),
+        err @ (ConfigError::InvalidRule(_)
+        | ConfigError::RuleNotFound(_)
+        | ConfigError::InvalidInbound(_)
+        | ConfigError::InboundNotFound(_)
+        | ConfigError::InvalidDns(_)
+        | ConfigError::InvalidPolicy(_)) => {
+            (StatusCode::BAD_REQUEST, ParameterIncorrect, err.to_string())
+        }
+        err => log_internal_error(err),
+    }
+}
+
 // Tell axum how to convert `AppError` into a response.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
@@ -131,10 +173,39 @@ impl IntoResponse for AppError {
                         ParameterIncorrect,
                         "Empty content".to_string(),
                     ),
+                    error::SubscriptionError::NodeNotFound(id) => (
+                        StatusCode::BAD_REQUEST,
+                        ParameterIncorrect,
+                        format!("Node {} not found", id),
+                    ),
+                    error::SubscriptionError::UnsupportedNode(node_type) => (
+                        StatusCode::BAD_REQUEST,
+                        ParameterIncorrect,
+                        format!("Unsupported node type {}", node_type),
+                    ),
                 },
+                VenusError::Config(err) => config_error_response(err),
+                VenusError::GeoChecksum { .. } => {
+                    (StatusCode::BAD_GATEWAY, InternalError, err.to_string())
+                }
+                VenusError::InvalidCustomList(_)
+                | VenusError::CustomListNotFound(_)
+                | VenusError::InvalidDomain(_) => {
+                    (StatusCode::BAD_REQUEST, ParameterIncorrect, err.to_string())
+                }
+                VenusError::CoreNotRunning => (
+                    StatusCode::BAD_REQUEST,
+                    ParameterIncorrect,
+                    "Core not running".to_string(),
+                ),
+                VenusError::CoreConfigTest(message) => (
+                    StatusCode::BAD_REQUEST,
+                    ParameterIncorrect,
+                    format!("Core config test failed: {}", message),
+                ),
                 _ => log_internal_error(err),
             },
-            AppError::VenusConfig(err) => log_internal_error(err),
+            AppError::VenusConfig(err) => config_error_response(err),
             AppError::GlobalPoison(err) => log_internal_error(err),
             AppError::Any(err) => log_internal_error(err),
             AppError::Jwt(err) => log_internal_error(err),
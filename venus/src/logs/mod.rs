@@ -0,0 +1,327 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+use venus_core::core_log::{AccessLog, CoreLogEntry, CoreLogLevel};
+
+use crate::consts::VENUS_LOG_CAPACITY;
+
+/// Name of the span core output is re-emitted in, see `main`
+pub const CORE_SPAN: &str = "CORE";
+/// Default page size of `LogBuffer::query`
+pub const DEFAULT_LOG_PAGE_SIZE: usize = 100;
+/// Max page size of `LogBuffer::query`
+pub const MAX_LOG_PAGE_SIZE: usize = 500;
+/// How many entries a slow stream subscriber can fall behind
+pub const LOG_STREAM_CAPACITY: usize = 256;
+
+/// Where a log entry came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LogSource {
+    /// v2ray core output
+    Core,
+    /// venus server itself
+    Venus,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    /// Increasing id, stays unique after old entries are dropped
+    pub id: u64,
+    /// When venus received the entry
+    pub timestamp: DateTime<Utc>,
+    pub level: CoreLogLevel,
+    pub source: LogSource,
+    /// Core package or venus module path
+    pub component: Option<String>,
+    pub message: String,
+    pub access: Option<AccessLog>,
+}
+
+/// Filters of `GET /api/logs` and `GET /api/logs/stream`
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogQuery {
+    /// Minimum level
+    pub level: Option<CoreLogLevel>,
+    pub source: Option<LogSource>,
+    pub since: Option<DateTime<Utc>>,
+    /// Case insensitive keyword in component or message
+    pub q: Option<String>,
+    /// Starts from 1
+    pub page: Option<usize>,
+    pub size: Option<usize>,
+}
+
+impl LogQuery {
+    /// Whether the entry passes `level`, `source`, `since` and `q`
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.level.is_none_or(|level| entry.level >= level)
+            && self.source.is_none_or(|source| entry.source == source)
+            && self.since.is_none_or(|since| entry.timestamp >= since)
+            && self.q.as_ref().is_none_or(|q| {
+                let keyword = q.to_lowercase();
+                entry.message.to_lowercase().contains(&keyword)
+                    || entry
+                        .component
+                        .as_ref()
+                        .is_some_and(|c| c.to_lowercase().contains(&keyword))
+            })
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPage {
+    /// Count of entries matching the filters
+    pub total: usize,
+    pub page: usize,
+    pub size: usize,
+    /// Newest first
+    pub entries: Vec<LogEntry>,
+}
+
+/// Bounded buffer of the latest log entries, the oldest entry is dropped when full
+///
+/// New entries are also broadcast to `subscribe`rs.
+#[derive(Debug)]
+pub struct LogBuffer {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+    next_id: u64,
+    tx: Sender<LogEntry>,
+}
+
+impl LogBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+            next_id: 0,
+            tx: broadcast::channel(LOG_STREAM_CAPACITY).0,
+        }
+    }
+
+    /// Receive entries pushed from now on
+    pub fn subscribe(&self) -> Receiver<LogEntry> {
+        self.tx.subscribe()
+    }
+
+    /// Append an entry, `id` and `timestamp` are assigned here
+    pub fn push(
+        &mut self,
+        level: CoreLogLevel,
+        source: LogSource,
+        component: Option<String>,
+        message: String,
+        access: Option<AccessLog>,
+    ) -> LogEntry {
+        if self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        let entry = LogEntry {
+            id: self.next_id,
+            timestamp: Utc::now(),
+            level,
+            source,
+            component,
+            message,
+            access,
+        };
+        self.next_id += 1;
+        if self.capacity > 0 {
+            self.entries.push_back(entry.clone());
+        }
+        // no stream is listening
+        self.tx.send(entry.clone()).ok();
+        entry
+    }
+
+    /// Append a parsed core log line
+    pub fn push_core(&mut self, entry: CoreLogEntry) -> LogEntry {
+        self.push(
+            entry.level,
+            LogSource::Core,
+            entry.component,
+            entry.message,
+            entry.access,
+        )
+    }
+
+    /// Filter entries and return one page, newest first
+    pub fn query(&self, query: &LogQuery) -> LogPage {
+        let page = query.page.unwrap_or(1).max(1);
+        let size = query
+            .size
+            .unwrap_or(DEFAULT_LOG_PAGE_SIZE)
+            .clamp(1, MAX_LOG_PAGE_SIZE);
+
+        let matched = self
+            .entries
+            .iter()
+            .rev()
+            .filter(|entry| query.matches(entry));
+
+        let mut total = 0;
+        let mut entries = vec![];
+        for (i, entry) in matched.enumerate() {
+            total += 1;
+            if i >= (page - 1) * size && entries.len() < size {
+                entries.push(entry.clone());
+            }
+        }
+        LogPage {
+            total,
+            page,
+            size,
+            entries,
+        }
+    }
+}
+
+static LOGS: LazyLock<Mutex<LogBuffer>> =
+    LazyLock::new(|| Mutex::new(LogBuffer::new(*VENUS_LOG_CAPACITY)));
+/// Recent core and venus logs
+///
+/// A std mutex, it is locked from the tracing layer which cannot await.
+pub fn global_logs() -> &'static Mutex<LogBuffer> {
+    &LOGS
+}
+
+/// Collects the `message` field and appends other fields as `key=value`
+#[derive(Default)]
+struct MessageVisitor(String);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            write!(self.0, "{value:?}").ok();
+        } else {
+            write!(self.0, " {}={value:?}", field.name()).ok();
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0.push_str(value);
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+}
+
+/// Tracing layer copying venus events into `global_logs`
+///
+/// Events inside the `CORE` span are skipped, they are already
+/// pushed as core entries.
+pub struct LogBufferLayer;
+
+impl<S> Layer<S> for LogBufferLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let in_core = ctx
+            .event_scope(event)
+            .is_some_and(|mut scope| scope.any(|span| span.name() == CORE_SPAN));
+        if in_core {
+            return;
+        }
+
+        let metadata = event.metadata();
+        let level = match *metadata.level() {
+            Level::ERROR => CoreLogLevel::Error,
+            Level::WARN => CoreLogLevel::Warning,
+            Level::INFO => CoreLogLevel::Info,
+            _ => CoreLogLevel::Debug,
+        };
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let Ok(mut logs) = global_logs().lock() else {
+            return;
+        };
+        logs.push(
+            level,
+            LogSource::Venus,
+            Some(metadata.target().to_string()),
+            visitor.0,
+            None,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer() -> LogBuffer {
+        let mut logs = LogBuffer::new(3);
+        logs.push(CoreLogLevel::Info, LogSource::Venus, None, "a".into(), None);
+        logs.push_core(CoreLogEntry::parse(
+            "2025/01/02 15:04:05 [Warning] app/dispatcher: default route",
+        ));
+        logs.push(
+            CoreLogLevel::Error,
+            LogSource::Venus,
+            None,
+            "b".into(),
+            None,
+        );
+        logs.push(
+            CoreLogLevel::Debug,
+            LogSource::Venus,
+            None,
+            "c".into(),
+            None,
+        );
+        logs
+    }
+
+    #[test]
+    fn test_log_buffer_drops_oldest() {
+        let page = buffer().query(&LogQuery::default());
+        assert_eq!(page.total, 3);
+        let ids: Vec<_> = page.entries.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn test_log_buffer_query() {
+        let logs = buffer();
+        let page = logs.query(&LogQuery {
+            level: Some(CoreLogLevel::Warning),
+            ..Default::default()
+        });
+        assert_eq!(page.total, 2);
+
+        let page = logs.query(&LogQuery {
+            source: Some(LogSource::Core),
+            q: Some("DISPATCHER".into()),
+            ..Default::default()
+        });
+        assert_eq!(page.total, 1);
+        assert_eq!(page.entries[0].message, "default route");
+
+        let page = logs.query(&LogQuery {
+            page: Some(2),
+            size: Some(2),
+            ..Default::default()
+        });
+        assert_eq!(page.total, 3);
+        assert_eq!(page.entries.len(), 1);
+        assert_eq!(page.entries[0].id, 1);
+    }
+}
@@ -1,19 +1,21 @@
-use core::{global_core, global_message};
+use core::{global_core, global_message, spawn_config_watcher};
 use std::{env, error::Error, net::SocketAddr};
 
 use anyhow::Context;
 use axum::Router;
 use consts::{DEFAULT_PORT, RUA_COMPILER};
 use dotenvy::dotenv;
+use logs::{global_logs, CORE_SPAN};
 use routes::routes;
-use tokio::net::TcpListener;
-use tracing::{info, span, Level};
+use tokio::{net::TcpListener, sync::broadcast::error::RecvError};
+use tracing::{debug, error, info, span, warn, Instrument, Level};
 use utils::{init_logger, shutdown_cb, shutdown_signal};
-use venus_core::{message::MessageType, VenusCore};
+use venus_core::{consts::VENUS_CONFIG_WATCH, core_log::CoreLogLevel, message::MessageType};
 
 mod consts;
 mod core;
 mod error;
+mod logs;
 mod middlewares;
 mod routes;
 mod utils;
@@ -25,6 +27,38 @@ async fn main() -> Result<()> {
     dotenv().ok();
     init_logger();
 
+    // subscribe before the core starts, so no early output is missed
+    let mut core_rx = global_message().await.subscribe();
+    tokio::spawn(
+        async move {
+            // global message handler
+            loop {
+                match core_rx.recv().await {
+                    Ok(MessageType::Core(entry)) => {
+                        match entry.level {
+                            CoreLogLevel::Debug => debug!("{entry}"),
+                            CoreLogLevel::Info => info!("{entry}"),
+                            CoreLogLevel::Warning => warn!("{entry}"),
+                            CoreLogLevel::Error => error!("{entry}"),
+                        }
+                        if let Ok(mut logs) = global_logs().lock() {
+                            logs.push_core(entry);
+                        }
+                    }
+                    // keep listening, the core can be started again through the api
+                    Ok(MessageType::Terminate) => {
+                        info!("core stopping");
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("core message handler lagged, {skipped} messages skipped");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+        .instrument(span!(Level::INFO, CORE_SPAN)),
+    );
+
     {
         info!("venus {RUA_COMPILER}");
         let venus = &mut global_core().await.lock().await;
@@ -38,28 +72,20 @@ async fn main() -> Result<()> {
             .reload_core()
             .with_context(|| "reading core configuration failed")?;
         venus
-            .config
-            .write_core()
-            .with_context(|| "write core configuration failed")?;
-        venus.spawn_core().with_context(|| "staring core failed")?;
-    }
-    tokio::spawn(async move {
-        // global message handler
-        let child_rx = &global_message().await.lock().await.1;
-        let core_span = span!(Level::INFO, "CORE").entered();
-        while let Ok(msg) = child_rx.recv() {
-            match msg {
-                MessageType::Core(msg) => {
-                    info!("{msg}");
-                }
-                MessageType::Terminate => {
-                    info!("core stopping");
-                    break;
-                }
-            }
+            .apply_core()
+            .await
+            .with_context(|| "staring core failed")?;
+        if !venus.follow_core_log() {
+            warn!("core api inbound not found, reading core logs from stdout only");
         }
-        core_span.exit();
-    });
+        // picks up edits made while venus was stopped
+        if let Err(err) = venus.config.record_history("venus", "startup") {
+            warn!("recording config history failed {err}");
+        }
+    }
+    if *VENUS_CONFIG_WATCH {
+        spawn_config_watcher().with_context(|| "watching config files failed")?;
+    }
 
     let port = env::var("VENUS_PORT")
         .map(|port| port.parse::<u16>().unwrap_or(DEFAULT_PORT))
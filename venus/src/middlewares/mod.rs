@@ -47,7 +47,12 @@ pub fn logging_route(router: Router) -> Router {
             .to_str()
             .unwrap_or("Unknown");
         let host = headers.get("Host").unwrap_or(empty).to_str().unwrap_or("");
-        info_span!("HTTP", method = ?req.method(), host, uri = ?req.uri(), ua)
+        // keep the jwt of `/api/logs/stream` out of the logs
+        let uri = match req.uri().query() {
+            Some(query) if query.contains("token=") => req.uri().path(),
+            _ => req.uri().path_and_query().map_or("/", |uri| uri.as_str()),
+        };
+        info_span!("HTTP", method = ?req.method(), host, uri, ua)
     };
 
     let trace_layer = TraceLayer::new_for_http()
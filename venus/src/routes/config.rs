@@ -0,0 +1,235 @@
+use axum::{
+    extract::{Path, Query},
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use venus_core::{
+    config::{
+        backup::{Backup, BackupKind},
+        history::{DiffEntry, SnapshotInfo},
+        raw::ConfigIssue,
+        watcher::FileSyncStatus,
+    },
+    error::log_err,
+    validate_core_raw,
+};
+
+use crate::{
+    core::global_core,
+    utils::{jwt::Claims, validator::ValidatedJson},
+};
+
+use super::{RouteResponse, RouteResult};
+
+/// Backups of venus and core config, newest first
+pub async fn backups(_claims: Claims) -> RouteResult<Vec<Backup>> {
+    let core = global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.config.list_backups()?,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RestorePayload {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    pub name: String,
+}
+
+/// Restore a config backup
+///
+/// A restored core config is checked by the core first, then applied by
+/// restarting the core.
+pub async fn restore(
+    claims: Claims,
+    ValidatedJson(payload): ValidatedJson<RestorePayload>,
+) -> RouteResult<()> {
+    let core = &mut global_core().await.lock().await;
+    core.restore_backup(&payload.name).await?;
+    let reason = format!("restore backup {}", payload.name);
+    core.config
+        .record_history(&claims.sub, &reason)
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    })
+}
+
+/// Recorded config changes, newest first
+pub async fn history(_claims: Claims) -> RouteResult<Vec<SnapshotInfo>> {
+    let core = global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.config.history()?,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Structural diff between two snapshots
+///
+/// `GET /api/config/history/diff?from=1&to=3`
+pub async fn history_diff(
+    _claims: Claims,
+    Query(DiffQuery { from, to }): Query<DiffQuery>,
+) -> RouteResult<Vec<DiffEntry>> {
+    let core = global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.config.history_diff(from, to)?,
+        ..RouteResponse::default()
+    })
+}
+
+/// Revert venus and core config to a snapshot
+///
+/// The revert itself is recorded as a new snapshot.
+pub async fn revert(claims: Claims, Path(id): Path<u64>) -> RouteResult<Option<SnapshotInfo>> {
+    let core = &mut global_core().await.lock().await;
+    core.revert(id).await?;
+    let snapshot = core
+        .config
+        .record_history(&claims.sub, &format!("revert to snapshot {id}"))
+        .map_err(log_err)
+        .ok()
+        .flatten();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: snapshot,
+        ..RouteResponse::default()
+    })
+}
+
+/// Whether the config files were edited outside venus
+pub async fn sync(_claims: Claims) -> RouteResult<Vec<FileSyncStatus>> {
+    let core = global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.config.sync_statuses()?,
+        ..RouteResponse::default()
+    })
+}
+
+/// Which side of a conflict is kept
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeepSide {
+    /// Reload the file, in-memory changes are discarded
+    Disk,
+    /// Overwrite the file with the in-memory config
+    Memory,
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvePayload {
+    pub kind: BackupKind,
+    pub keep: KeepSide,
+}
+
+/// Resolve a config file edited outside venus
+pub async fn resolve(
+    claims: Claims,
+    ValidatedJson(payload): ValidatedJson<ResolvePayload>,
+) -> RouteResult<Vec<FileSyncStatus>> {
+    let ResolvePayload { kind, keep } = payload;
+    let core = &mut global_core().await.lock().await;
+    match keep {
+        KeepSide::Disk => {
+            core.reload_external(kind, true).await?;
+        }
+        KeepSide::Memory => core.config.overwrite(kind)?,
+    }
+    let reason = format!("resolve {kind:?} config keeping {keep:?}").to_lowercase();
+    core.config
+        .record_history(&claims.sub, &reason)
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.config.sync_statuses()?,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RawCorePayload {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    pub content: String,
+    /// (Re)start the core after saving
+    #[serde(default)]
+    pub restart: bool,
+}
+
+/// Exact text of the core `config.json`, comments included
+pub async fn core_raw(_claims: Claims) -> RouteResult<RawCorePayload> {
+    let core = global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: RawCorePayload {
+            content: core.config.read_core_raw()?,
+            restart: false,
+        },
+        ..RouteResponse::default()
+    })
+}
+
+/// Save the core `config.json` as is
+///
+/// The text is validated by parsing and by the core's test mode first.
+/// Parse errors are returned with their line and column.
+pub async fn save_core_raw(
+    claims: Claims,
+    ValidatedJson(payload): ValidatedJson<RawCorePayload>,
+) -> RouteResult<()> {
+    let core = &mut global_core().await.lock().await;
+    core.apply_core_raw(&payload.content, payload.restart)
+        .await?;
+    core.config
+        .record_history(&claims.sub, "edit core config")
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    })
+}
+
+/// Check core `config.json` text without saving it
+///
+/// Problems are returned as data, an empty list means the config is valid.
+pub async fn validate_raw(
+    _claims: Claims,
+    ValidatedJson(payload): ValidatedJson<RawCorePayload>,
+) -> RouteResult<Vec<ConfigIssue>> {
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: validate_core_raw(&payload.content).await?,
+        ..RouteResponse::default()
+    })
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/backups", get(backups))
+        .route("/backups/restore", post(restore))
+        .route("/history", get(history))
+        .route("/history/diff", get(history_diff))
+        .route("/revert/{id}", post(revert))
+        .route("/sync", get(sync))
+        .route("/sync/resolve", post(resolve))
+        .route("/core/raw", get(core_raw).put(save_core_raw))
+        .route("/core/raw/validate", post(validate_raw))
+}
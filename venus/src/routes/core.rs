@@ -0,0 +1,127 @@
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use venus_core::{
+    config::error::ConfigError, core_log::CoreLogLevel, error::log_err, grpc::log::restart_logger,
+    status::CoreStatus, VenusCore,
+};
+
+use crate::{
+    core::global_core,
+    utils::{jwt::Claims, validator::ValidatedJson},
+};
+
+use super::{RouteResponse, RouteResult};
+
+/// Current core process status
+pub async fn status(_claims: Claims) -> RouteResult<CoreStatus> {
+    let core = &mut global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.core_status(),
+        ..RouteResponse::default()
+    })
+}
+
+/// Start the core process
+pub async fn start(_claims: Claims) -> RouteResult<CoreStatus> {
+    let core = &mut global_core().await.lock().await;
+    core.spawn_core().await?;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.core_status(),
+        ..RouteResponse::default()
+    })
+}
+
+/// Stop the core process
+pub async fn stop(_claims: Claims) -> RouteResult<CoreStatus> {
+    let core = &mut global_core().await.lock().await;
+    core.kill_core().await?;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.core_status(),
+        ..RouteResponse::default()
+    })
+}
+
+/// Restart the core process
+pub async fn restart(_claims: Claims) -> RouteResult<CoreStatus> {
+    let core = &mut global_core().await.lock().await;
+    core.restart().await?;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.core_status(),
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct LogLevelPayload {
+    pub level: CoreLogLevel,
+}
+
+/// Current runtime log level of the core
+pub async fn log_level(_claims: Claims) -> RouteResult<LogLevelPayload> {
+    let core = global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: LogLevelPayload {
+            level: core.log_level(),
+        },
+        ..RouteResponse::default()
+    })
+}
+
+/// Change the core log level without restarting the core
+pub async fn set_log_level(
+    claims: Claims,
+    ValidatedJson(payload): ValidatedJson<LogLevelPayload>,
+) -> RouteResult<LogLevelPayload> {
+    let core = &mut global_core().await.lock().await;
+    core.set_log_level(payload.level)?;
+    let reason = format!("set core log level {}", payload.level.as_str());
+    core.config
+        .record_history(&claims.sub, &reason)
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: payload,
+        ..RouteResponse::default()
+    })
+}
+
+/// Restart the core's logger through its api, e.g. after log files were rotated
+pub async fn restart_core_logger(_claims: Claims) -> RouteResult<()> {
+    let url = global_core()
+        .await
+        .lock()
+        .await
+        .config
+        .core
+        .as_ref()
+        .and_then(|core| core.api_url())
+        .ok_or(ConfigError::Empty(
+            "core api inbound is not configured".into(),
+        ))?;
+    restart_logger(url).await?;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    })
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/status", get(status))
+        .route("/start", post(start))
+        .route("/stop", post(stop))
+        .route("/restart", post(restart))
+        .route("/log-level", get(log_level).post(set_log_level))
+        .route("/logger/restart", post(restart_core_logger))
+}
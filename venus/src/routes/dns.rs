@@ -0,0 +1,86 @@
+use axum::{routing::post, Router};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use venus_core::{
+    config::dns::DnsSetting,
+    error::log_err,
+    resolver::{test_dns, DnsTestResult},
+};
+
+use crate::{
+    core::global_core,
+    utils::{jwt::Claims, validator::ValidatedJson},
+};
+
+use super::{RouteResponse, RouteResult};
+
+/// Current DNS servers, hosts and fake DNS pool
+pub async fn dns(_claims: Claims) -> RouteResult<DnsSetting> {
+    let core = global_core().await.lock().await;
+    let setting = core.config.core_config()?.dns_setting();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: setting,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct DnsPayload {
+    #[serde(flatten)]
+    pub setting: DnsSetting,
+}
+
+/// Replace the DNS setting and apply it
+pub async fn set_dns(
+    claims: Claims,
+    ValidatedJson(DnsPayload { setting }): ValidatedJson<DnsPayload>,
+) -> RouteResult<DnsSetting> {
+    let core = &mut global_core().await.lock().await;
+    core.modify_core(|core| core.set_dns(&setting)).await?;
+    core.config
+        .record_history(&claims.sub, "update dns")
+        .map_err(log_err)
+        .ok();
+    let setting = core
+        .config
+        .core
+        .as_ref()
+        .map(|core| core.dns_setting())
+        .unwrap_or(setting);
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: setting,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct DnsTestPayload {
+    #[validate(length(min = 1, max = 253))]
+    pub domain: String,
+}
+
+/// Resolve a domain like the core's DNS would
+///
+/// The servers are queried after the core lock is released.
+pub async fn test(
+    _claims: Claims,
+    ValidatedJson(DnsTestPayload { domain }): ValidatedJson<DnsTestPayload>,
+) -> RouteResult<DnsTestResult> {
+    let (core_config, geodata) = {
+        let core = &mut global_core().await.lock().await;
+        let core_config = core.config.core_config()?.clone();
+        (core_config, core.geodata().ok())
+    };
+    let result = test_dns(&core_config, geodata.as_deref(), &domain).await?;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: result,
+        ..RouteResponse::default()
+    })
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/test", post(test))
+}
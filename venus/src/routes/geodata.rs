@@ -0,0 +1,197 @@
+use axum::{
+    extract::{Path, Query},
+    routing::{get, post, put},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use venus_core::{
+    error::{log_err, VenusError},
+    geodata::{
+        custom::{
+            custom_site_reference, list_custom_sites, remove_custom_sites, write_custom_sites,
+            CustomSiteList,
+        },
+        update::{download_geo, geo_files, GeoFile, GeoKind},
+        GeoCategory,
+    },
+    Venus,
+};
+
+use crate::{
+    core::global_core,
+    utils::{jwt::Claims, validator::ValidatedJson},
+};
+
+use super::{RouteResponse, RouteResult};
+
+/// Installed geoip and geosite files
+pub async fn files(_claims: Claims) -> RouteResult<Vec<GeoFile>> {
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: geo_files(),
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdatePayload {
+    /// Files to update, all when missing
+    pub kinds: Option<Vec<GeoKind>>,
+}
+
+/// Download geo data files in the background, then restart the core with them
+///
+/// Downloads can take longer than the request timeout, `GET /api/geodata`
+/// shows the new files once they are swapped in.
+///
+/// # Returns
+/// * Files being updated
+pub async fn update(
+    _claims: Claims,
+    ValidatedJson(payload): ValidatedJson<UpdatePayload>,
+) -> RouteResult<Vec<GeoKind>> {
+    let kinds = payload.kinds.unwrap_or(GeoKind::ALL.to_vec());
+    tokio::spawn(update_files(kinds.clone()));
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: kinds,
+        ..RouteResponse::default()
+    })
+}
+
+/// Download `kinds` without holding the core, it is reloaded when any file
+/// was swapped in even if another download failed
+async fn update_files(kinds: Vec<GeoKind>) {
+    let mut swapped = false;
+    for kind in kinds {
+        swapped |= download_geo(kind).await.map_err(log_err).is_ok();
+    }
+    if swapped {
+        let core = &mut global_core().await.lock().await;
+        core.reload_geodata().await.map_err(log_err).ok();
+    }
+}
+
+/// Index of the first routing rule using a custom site list
+fn custom_list_user(core: &Venus, name: &str) -> Option<usize> {
+    let reference = custom_site_reference(name);
+    core.config
+        .core
+        .as_ref()?
+        .routing
+        .rules
+        .iter()
+        .position(|rule| {
+            rule.domain
+                .iter()
+                .flatten()
+                .any(|domain| domain == &reference)
+        })
+}
+
+/// Custom site lists
+pub async fn custom_lists(_claims: Claims) -> RouteResult<Vec<CustomSiteList>> {
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: list_custom_sites()?,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomSitesPayload {
+    pub domains: Vec<String>,
+}
+
+/// Create or replace a custom site list
+///
+/// The core is restarted when a routing rule already uses the list.
+pub async fn save_custom_list(
+    _claims: Claims,
+    Path(name): Path<String>,
+    ValidatedJson(payload): ValidatedJson<CustomSitesPayload>,
+) -> RouteResult<CustomSiteList> {
+    let list = write_custom_sites(&name, &payload.domains)?;
+    let core = &mut global_core().await.lock().await;
+    if custom_list_user(core, &name).is_some() {
+        core.reload_geodata().await?;
+    }
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: list,
+        ..RouteResponse::default()
+    })
+}
+
+/// Delete a custom site list no routing rule uses
+pub async fn delete_custom_list(_claims: Claims, Path(name): Path<String>) -> RouteResult<()> {
+    let core = global_core().await.lock().await;
+    if let Some(index) = custom_list_user(&core, &name) {
+        return Err(VenusError::InvalidCustomList(format!(
+            "{name} is used by routing rule #{index}"
+        ))
+        .into());
+    }
+    remove_custom_sites(&name)?;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    })
+}
+
+/// `geosite:` categories with their domain counts
+pub async fn sites(_claims: Claims) -> RouteResult<Vec<GeoCategory>> {
+    let core = &mut global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.geodata()?.site_categories(),
+        ..RouteResponse::default()
+    })
+}
+
+/// `geoip:` categories with their CIDR counts
+pub async fn ips(_claims: Claims) -> RouteResult<Vec<GeoCategory>> {
+    let core = &mut global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.geodata()?.ip_categories(),
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LookupQuery {
+    /// Domain or IP
+    pub target: String,
+}
+
+/// Categories containing a domain or IP
+///
+/// `GET /api/geodata/lookup?target=www.google.com`
+pub async fn lookup(
+    _claims: Claims,
+    Query(LookupQuery { target }): Query<LookupQuery>,
+) -> RouteResult<Vec<String>> {
+    let core = &mut global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.geodata()?.lookup(&target),
+        ..RouteResponse::default()
+    })
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/sites", get(sites))
+        .route("/ips", get(ips))
+        .route("/lookup", get(lookup))
+        .route("/update", post(update))
+        .route("/custom", get(custom_lists))
+        .route(
+            "/custom/{name}",
+            put(save_custom_list).delete(delete_custom_list),
+        )
+}
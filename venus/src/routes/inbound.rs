@@ -0,0 +1,111 @@
+use axum::{
+    extract::Path,
+    routing::{get, put},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use venus_core::{
+    config::{
+        inbound::{InboundUpdate, LanSetting},
+        types::Inbound,
+    },
+    error::log_err,
+};
+
+use crate::{
+    core::global_core,
+    utils::{jwt::Claims, validator::ValidatedJson},
+};
+
+use super::{RouteResponse, RouteResult};
+
+/// Core inbounds
+pub async fn inbounds(_claims: Claims) -> RouteResult<Vec<Inbound>> {
+    let core = global_core().await.lock().await;
+    let inbounds = core.config.core_config()?.inbounds.clone();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: inbounds,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct InboundPayload {
+    #[serde(flatten)]
+    pub update: InboundUpdate,
+}
+
+/// Change port, listen address, accounts, udp or sniffing of a socks/http inbound
+pub async fn update_inbound(
+    claims: Claims,
+    Path(tag): Path<String>,
+    ValidatedJson(InboundPayload { update }): ValidatedJson<InboundPayload>,
+) -> RouteResult<()> {
+    let core = &mut global_core().await.lock().await;
+    core.modify_core(|core| core.update_inbound(&tag, update))
+        .await?;
+    core.config
+        .record_history(&claims.sub, &format!("update inbound {tag}"))
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    })
+}
+
+/// Whether the proxy inbounds are open to the LAN
+pub async fn lan(_claims: Claims) -> RouteResult<LanSetting> {
+    let core = global_core().await.lock().await;
+    let allow = core
+        .config
+        .core
+        .as_ref()
+        .is_some_and(|core| core.allow_lan());
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: LanSetting {
+            allow,
+            allowlist: core.config.venus.settings.lan_allowlist.clone(),
+        },
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct LanPayload {
+    #[serde(flatten)]
+    pub lan: LanSetting,
+}
+
+/// Open the proxy inbounds to the LAN, optionally to listed sources only
+pub async fn set_lan(
+    claims: Claims,
+    ValidatedJson(LanPayload { lan }): ValidatedJson<LanPayload>,
+) -> RouteResult<()> {
+    let core = &mut global_core().await.lock().await;
+    core.modify_core(|core| core.set_lan(&lan)).await?;
+    core.config.venus.settings.lan_allowlist = lan.allowlist;
+    core.config.write_rua()?;
+    let reason = if lan.allow {
+        "allow lan access"
+    } else {
+        "disallow lan access"
+    };
+    core.config
+        .record_history(&claims.sub, reason)
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    })
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/lan", get(lan).put(set_lan))
+        .route("/{tag}", put(update_inbound))
+}
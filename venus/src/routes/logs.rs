@@ -0,0 +1,66 @@
+use std::{convert::Infallible, sync::PoisonError};
+
+use axum::{
+    extract::Query,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::get,
+    Router,
+};
+use serde::Deserialize;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use crate::{
+    error::AppResult,
+    logs::{global_logs, LogPage, LogQuery},
+    utils::jwt::{verify_jwt, Claims},
+};
+
+use super::{RouteResponse, RouteResult};
+
+/// Recent core and venus logs, newest first
+///
+/// `GET /api/logs?level=warning&source=core&since=2025-01-02T15:04:05Z&q=dispatcher&page=1&size=100`
+pub async fn logs(_claims: Claims, Query(query): Query<LogQuery>) -> RouteResult<LogPage> {
+    let page = global_logs()
+        .lock()
+        .map(|logs| logs.query(&query))
+        .unwrap_or_default();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: page,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StreamToken {
+    token: String,
+}
+
+/// Push new log entries as server-sent `log` events
+///
+/// `EventSource` cannot set headers, so the jwt is passed as `token`.
+/// Filters are the same as `logs`, `page` and `size` are ignored.
+///
+/// `GET /api/logs/stream?token=<jwt>&level=warning&source=core&q=dispatcher`
+pub async fn stream(
+    Query(StreamToken { token }): Query<StreamToken>,
+    Query(query): Query<LogQuery>,
+) -> AppResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    verify_jwt(&token)?;
+    let rx = global_logs()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .subscribe();
+
+    // lagged entries are skipped, they can be fetched from `logs`
+    let events = BroadcastStream::new(rx).filter_map(move |entry| {
+        let entry = entry.ok().filter(|entry| query.matches(entry))?;
+        Event::default().event("log").json_data(entry).ok().map(Ok)
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/stream", get(stream))
+}
@@ -22,8 +22,18 @@ use crate::{
     middlewares::{add_version, logging_route},
 };
 
+pub mod config;
+pub mod core;
+pub mod dns;
+pub mod geodata;
+pub mod inbound;
+pub mod logs;
+pub mod policy;
 pub mod proxies;
+pub mod routing;
 pub mod stats;
+pub mod tproxy;
+pub mod tun;
 pub mod user;
 pub mod version;
 
@@ -58,9 +68,25 @@ pub fn routes() -> Router {
             "/api/",
             Router::new()
                 .route("/version", get(version::version))
+                .route("/logs", get(logs::logs))
+                .nest("/logs", logs::routes())
                 .nest("/user", user::routes())
                 .nest("/subscription", proxies::routes())
-                .nest("/stats", stats::routes()),
+                .nest("/stats", stats::routes())
+                .nest("/core", self::core::routes())
+                .nest("/config", config::routes())
+                .nest("/routing", routing::routes())
+                .route("/geodata", get(geodata::files))
+                .nest("/geodata", geodata::routes())
+                .route("/inbounds", get(inbound::inbounds))
+                .nest("/inbounds", inbound::routes())
+                .route("/tproxy", get(tproxy::tproxy).put(tproxy::set_tproxy))
+                .nest("/tproxy", tproxy::routes())
+                .route("/tun", get(tun::tun).put(tun::set_tun))
+                .nest("/tun", tun::routes())
+                .route("/dns", get(dns::dns).put(dns::set_dns))
+                .nest("/dns", dns::routes())
+                .route("/policy", get(policy::policy).put(policy::set_policy)),
         )
         .layer(
             ServiceBuilder::new()
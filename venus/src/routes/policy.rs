@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use venus_core::{config::types::Policy, error::log_err};
+
+use crate::{
+    core::global_core,
+    utils::{jwt::Claims, validator::ValidatedJson},
+};
+
+use super::{RouteResponse, RouteResult};
+
+/// Current policy levels and system stats switches
+pub async fn policy(_claims: Claims) -> RouteResult<Policy> {
+    let core = global_core().await.lock().await;
+    let policy = core.config.core_config()?.policy.clone();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: policy,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct PolicyPayload {
+    #[serde(flatten)]
+    pub policy: Policy,
+}
+
+/// Replace the policy and apply it
+pub async fn set_policy(
+    claims: Claims,
+    ValidatedJson(PolicyPayload { policy }): ValidatedJson<PolicyPayload>,
+) -> RouteResult<Policy> {
+    let core = &mut global_core().await.lock().await;
+    core.modify_core(|core| core.set_policy(policy.clone()))
+        .await?;
+    core.config
+        .record_history(&claims.sub, "update policy")
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: policy,
+        ..RouteResponse::default()
+    })
+}
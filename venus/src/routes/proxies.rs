@@ -6,7 +6,11 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use validator::Validate;
-use venus_core::{config::types::Subscription, VenusSubscriptor};
+use venus_core::{
+    config::types::{NodeType, Outbound, Subscription},
+    error::{log_err, SubscriptionError, VenusError},
+    VenusSubscriptor,
+};
 
 use crate::{
     core::global_core,
@@ -34,12 +38,17 @@ pub struct SubPayload {
 /// Returns BadRequest if subscription already exists
 #[axum::debug_handler]
 pub async fn add_subscription(
-    _claims: Claims,
+    claims: Claims,
     ValidatedJson(payload): ValidatedJson<SubPayload>,
 ) -> AppResult<impl IntoResponse> {
     let SubPayload { name, url } = payload;
+    let reason = format!("add subscription {name}");
     let core = &mut global_core().await.lock().await;
     core.add_subscription(name, url).await?;
+    core.config
+        .record_history(&claims.sub, &reason)
+        .map_err(log_err)
+        .ok();
 
     let res: RouteResponse<Option<()>> = RouteResponse {
         message: Some("ok".into()),
@@ -60,8 +69,67 @@ pub async fn subscriptions(_claims: Claims) -> AppResult<impl IntoResponse> {
     Ok((StatusCode::OK, res))
 }
 
+#[derive(Debug, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectPayload {
+    #[validate(length(min = 1, message = "Can not be empty"))]
+    pub node_id: String,
+}
+
+/// Select a node as the current proxy
+///
+/// The proxy outbound is swapped live when possible,
+/// otherwise the core will be restarted.
+///
+/// # Errors
+///
+/// Returns BadRequest if node not exists or its type is not supported
+pub async fn select_node(
+    claims: Claims,
+    ValidatedJson(payload): ValidatedJson<SelectPayload>,
+) -> AppResult<impl IntoResponse> {
+    let SelectPayload { node_id } = payload;
+    let core = &mut global_core().await.lock().await;
+    let node = core
+        .config
+        .venus
+        .subscriptions
+        .iter()
+        .flat_map(|s| s.nodes.iter())
+        .find(|n| n.node_id.as_deref() == Some(node_id.as_str()))
+        .cloned()
+        .ok_or(VenusError::from(SubscriptionError::NodeNotFound(
+            node_id.clone(),
+        )))?;
+    if node.node_type != Some(NodeType::Vmess) {
+        let node_type = node
+            .node_type
+            .as_ref()
+            .map(|t| t.as_str())
+            .unwrap_or("unknown");
+        let err = SubscriptionError::UnsupportedNode(node_type.to_string());
+        return Err(VenusError::from(err).into());
+    }
+
+    core.apply_outbound(Outbound::from(&node)).await?;
+    let reason = format!("select node {node_id}");
+    core.config.venus.settings.current_id = node_id.into();
+    core.config.write_rua()?;
+    core.config
+        .record_history(&claims.sub, &reason)
+        .map_err(log_err)
+        .ok();
+
+    let res: RouteResponse<Option<()>> = RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    };
+    Ok((StatusCode::OK, res))
+}
+
 pub fn routes() -> Router {
     Router::new()
         .route("/add", post(add_subscription))
         .route("/list", get(subscriptions))
+        .route("/select", post(select_node))
 }
@@ -0,0 +1,221 @@
+use axum::{
+    extract::{Path, Query},
+    routing::{get, post, put},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use venus_core::{
+    config::{
+        backup::BackupKind,
+        error::ConfigResult,
+        matcher::{RouteMatch, RouteQuery},
+        routing::RoutingMode,
+        types::Rule,
+    },
+    error::log_err,
+    Venus,
+};
+
+use crate::{
+    core::global_core,
+    utils::{jwt::Claims, validator::ValidatedJson},
+};
+
+use super::{RouteResponse, RouteResult};
+
+/// Routing rules in match order
+pub async fn rules(_claims: Claims) -> RouteResult<Vec<Rule>> {
+    let core = global_core().await.lock().await;
+    let rules = core.config.core_config()?.routing.rules.clone();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: rules,
+        ..RouteResponse::default()
+    })
+}
+
+/// Reject `geosite:` and `geoip:` categories missing from the installed geo data
+fn check_geo_categories(core: &mut Venus, rules: &[Rule]) -> ConfigResult<()> {
+    // unreadable geo data is left for the core to report
+    let Ok(geodata) = core.geodata().map_err(log_err) else {
+        return Ok(());
+    };
+    for rule in rules {
+        geodata.validate_rule(rule)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct RulePayload {
+    pub rule: Rule,
+    /// Insert position, after the custom rules when missing. Ignored by update
+    pub index: Option<usize>,
+}
+
+/// Add a routing rule and apply it
+///
+/// # Returns
+/// * Index of the new rule
+pub async fn add_rule(
+    claims: Claims,
+    ValidatedJson(payload): ValidatedJson<RulePayload>,
+) -> RouteResult<usize> {
+    let RulePayload { rule, index } = payload;
+    let core = &mut global_core().await.lock().await;
+    check_geo_categories(core, std::slice::from_ref(&rule))?;
+    // custom rules go before the preset ones by default
+    let index = core
+        .modify_core(|core| {
+            let index = index.unwrap_or(core.preset_start());
+            core.insert_rule(Some(index), rule)
+        })
+        .await?;
+    core.config
+        .record_history(&claims.sub, &format!("add routing rule #{index}"))
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: index,
+        ..RouteResponse::default()
+    })
+}
+
+/// Replace the routing rule at `index` and apply it
+pub async fn update_rule(
+    claims: Claims,
+    Path(index): Path<usize>,
+    ValidatedJson(payload): ValidatedJson<RulePayload>,
+) -> RouteResult<()> {
+    let core = &mut global_core().await.lock().await;
+    check_geo_categories(core, std::slice::from_ref(&payload.rule))?;
+    core.modify_core(|core| core.update_rule(index, payload.rule))
+        .await?;
+    core.config
+        .record_history(&claims.sub, &format!("update routing rule #{index}"))
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    })
+}
+
+/// Remove the routing rule at `index` and apply it
+pub async fn delete_rule(claims: Claims, Path(index): Path<usize>) -> RouteResult<()> {
+    let core = &mut global_core().await.lock().await;
+    core.modify_core(|core| core.remove_rule(index)).await?;
+    core.config
+        .record_history(&claims.sub, &format!("delete routing rule #{index}"))
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ReorderPayload {
+    /// Current indexes in the new order, e.g. `[2, 0, 1]` moves the last rule first
+    pub order: Vec<usize>,
+}
+
+/// Reorder routing rules and apply them
+pub async fn reorder_rules(
+    claims: Claims,
+    ValidatedJson(payload): ValidatedJson<ReorderPayload>,
+) -> RouteResult<()> {
+    let core = &mut global_core().await.lock().await;
+    core.modify_core(|core| core.reorder_rules(&payload.order))
+        .await?;
+    core.config
+        .record_history(&claims.sub, "reorder routing rules")
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct ModePayload {
+    pub mode: RoutingMode,
+}
+
+/// Current routing mode
+pub async fn mode(_claims: Claims) -> RouteResult<RoutingMode> {
+    let core = global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.config.venus.settings.routing_mode,
+        ..RouteResponse::default()
+    })
+}
+
+/// Switch the routing mode and apply it
+///
+/// Preset rules of the previous mode are replaced, custom rules are kept.
+/// When the mode can not be saved the previous preset rules are put back.
+pub async fn set_mode(
+    claims: Claims,
+    ValidatedJson(ModePayload { mode }): ValidatedJson<ModePayload>,
+) -> RouteResult<RoutingMode> {
+    let core = &mut global_core().await.lock().await;
+    check_geo_categories(core, &mode.preset_rules())?;
+    // `write_rua` would refuse an edited config.toml after the rules are live
+    core.config.check_synced(BackupKind::Venus)?;
+    let previous = core.config.venus.settings.routing_mode;
+    core.modify_core(|core| core.switch_routing_mode(mode))
+        .await?;
+    core.config.venus.settings.routing_mode = mode;
+    if let Err(err) = core.config.write_rua() {
+        core.config.venus.settings.routing_mode = previous;
+        core.modify_core(|core| core.switch_routing_mode(previous))
+            .await
+            .map_err(log_err)
+            .ok();
+        return Err(err.into());
+    }
+    core.config
+        .record_history(&claims.sub, &format!("switch routing mode to {mode:?}"))
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: mode,
+        ..RouteResponse::default()
+    })
+}
+
+/// Which rule and outbound a connection is routed to
+///
+/// Works while the core is down, `coreOutboundTag` is added when it runs.
+///
+/// `GET /api/routing/test?target=www.example.com&port=443&network=tcp&inboundTag=socks`
+pub async fn test_route(
+    _claims: Claims,
+    Query(query): Query<RouteQuery>,
+) -> RouteResult<RouteMatch> {
+    let core = &mut global_core().await.lock().await;
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: core.test_route(&query).await?,
+        ..RouteResponse::default()
+    })
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/rules", get(rules).post(add_rule))
+        .route("/rules/{index}", put(update_rule).delete(delete_rule))
+        .route("/rules/reorder", post(reorder_rules))
+        .route("/mode", get(mode).post(set_mode))
+        .route("/test", get(test_route))
+}
@@ -0,0 +1,111 @@
+use std::{collections::BTreeSet, net::Ipv4Addr};
+
+use axum::{extract::Query, routing::get, Router};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use venus_core::{
+    config::tproxy::{tproxy_script, FirewallKind, TproxySetting},
+    error::log_err,
+    Venus,
+};
+
+use crate::{
+    core::global_core,
+    utils::{jwt::Claims, validator::ValidatedJson},
+};
+
+use super::{RouteResponse, RouteResult};
+
+/// Current transparent proxy setting
+pub async fn tproxy(_claims: Claims) -> RouteResult<TproxySetting> {
+    let core = global_core().await.lock().await;
+    let setting = core.config.core_config()?.tproxy();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: setting,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct TproxyPayload {
+    #[serde(flatten)]
+    pub setting: TproxySetting,
+}
+
+/// Turn the transparent proxy inbound on or off and apply it
+///
+/// Firewall rules are not touched, see `script`.
+pub async fn set_tproxy(
+    claims: Claims,
+    ValidatedJson(TproxyPayload { setting }): ValidatedJson<TproxyPayload>,
+) -> RouteResult<TproxySetting> {
+    let core = &mut global_core().await.lock().await;
+    core.modify_core(|core| core.set_tproxy(&setting)).await?;
+    let reason = if setting.enabled {
+        "enable transparent proxy"
+    } else {
+        "disable transparent proxy"
+    };
+    core.config
+        .record_history(&claims.sub, reason)
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: setting,
+        ..RouteResponse::default()
+    })
+}
+
+/// IPv4 addresses of subscription nodes and proxy outbounds,
+/// their traffic must not be captured again
+pub(super) fn server_ips(core: &Venus) -> Vec<String> {
+    let nodes = core
+        .config
+        .venus
+        .subscriptions
+        .iter()
+        .flat_map(|subscription| subscription.nodes.iter())
+        .map(|node| node.add.as_ref());
+    let outbounds = core
+        .config
+        .core
+        .iter()
+        .flat_map(|core| core.outbounds.iter())
+        .flat_map(|outbound| outbound.settings.vnext.iter())
+        .map(|server| server.address.as_ref());
+    nodes
+        .chain(outbounds)
+        .filter_map(|address| address.parse::<Ipv4Addr>().ok())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(|ip| ip.to_string())
+        .collect()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ScriptQuery {
+    #[serde(default)]
+    pub kind: FirewallKind,
+}
+
+/// Firewall script for the current setting, to review and run by hand
+///
+/// `GET /api/tproxy/script?kind=iptables`
+pub async fn script(
+    _claims: Claims,
+    Query(ScriptQuery { kind }): Query<ScriptQuery>,
+) -> RouteResult<String> {
+    let core = global_core().await.lock().await;
+    let setting = core.config.core_config()?.tproxy();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: tproxy_script(&setting, kind, &server_ips(&core)),
+        ..RouteResponse::default()
+    })
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/script", get(script))
+}
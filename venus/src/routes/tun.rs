@@ -0,0 +1,71 @@
+use axum::{routing::get, Router};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+use venus_core::{
+    config::tun::{tun_script, TunSetting},
+    error::log_err,
+};
+
+use crate::{
+    core::global_core,
+    utils::{jwt::Claims, validator::ValidatedJson},
+};
+
+use super::{tproxy::server_ips, RouteResponse, RouteResult};
+
+/// Current TUN setting
+pub async fn tun(_claims: Claims) -> RouteResult<TunSetting> {
+    let core = global_core().await.lock().await;
+    let setting = core.config.core_config()?.tun();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: setting,
+        ..RouteResponse::default()
+    })
+}
+
+#[derive(Debug, Deserialize, Serialize, Validate)]
+pub struct TunPayload {
+    #[serde(flatten)]
+    pub setting: TunSetting,
+}
+
+/// Turn the TUN service on or off and apply it
+///
+/// System routes are not touched, see `script`.
+pub async fn set_tun(
+    claims: Claims,
+    ValidatedJson(TunPayload { setting }): ValidatedJson<TunPayload>,
+) -> RouteResult<TunSetting> {
+    let core = &mut global_core().await.lock().await;
+    core.modify_core(|core| core.set_tun(&setting)).await?;
+    let reason = if setting.enabled {
+        "enable tun"
+    } else {
+        "disable tun"
+    };
+    core.config
+        .record_history(&claims.sub, reason)
+        .map_err(log_err)
+        .ok();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: setting,
+        ..RouteResponse::default()
+    })
+}
+
+/// Routing script for the current setting, to review and run by hand
+pub async fn script(_claims: Claims) -> RouteResult<String> {
+    let core = global_core().await.lock().await;
+    let setting = core.config.core_config()?.tun();
+    Ok(RouteResponse {
+        message: Some("ok".into()),
+        data: tun_script(&setting, &server_ips(&core))?,
+        ..RouteResponse::default()
+    })
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/script", get(script))
+}
@@ -55,19 +55,26 @@ where
             .extract::<TypedHeader<Authorization<Bearer>>>()
             .await
             .map_err(|_| RouteError::InvalidToken("Extract the token failed".into()))?;
-        // Decode the user data
-        let token_data = decode_jwt(bearer.token())
-            .map_err(|_| RouteError::InvalidToken("Deocde the token failed".into()))?;
-
-        let now = Utc::now().naive_utc().and_utc().timestamp();
-        if token_data.claims.exp <= (now as usize) {
-            return Err(AppError::Route(RouteError::InvalidToken(
-                "token expired".into(),
-            )));
-        }
+        verify_jwt(bearer.token())
+    }
+}
 
-        Ok(token_data.claims)
+/// Decode the token and check it is not expired
+///
+/// Used directly by routes that cannot send the `Authorization` header,
+/// e.g. `EventSource` in browsers.
+pub fn verify_jwt(token: &str) -> Result<Claims, AppError> {
+    let token_data = decode_jwt(token)
+        .map_err(|_| RouteError::InvalidToken("Deocde the token failed".into()))?;
+
+    let now = Utc::now().naive_utc().and_utc().timestamp();
+    if token_data.claims.exp <= (now as usize) {
+        return Err(AppError::Route(RouteError::InvalidToken(
+            "token expired".into(),
+        )));
     }
+
+    Ok(token_data.claims)
 }
 
 pub fn encode_jwt(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
@@ -3,7 +3,7 @@ use tracing::info;
 use tracing_subscriber::{fmt, prelude::*, registry, EnvFilter};
 use venus_core::{error::log_err, VenusCore};
 
-use crate::{core::global_core, error::AppResult};
+use crate::{core::global_core, error::AppResult, logs::LogBufferLayer};
 
 pub mod jwt;
 pub mod password;
@@ -33,15 +33,20 @@ pub fn init_logger() {
 
     let env_layer = EnvFilter::try_from_env("VENUS_LOG").unwrap_or_else(|_| "info".into());
 
-    registry().with(env_layer).with(formatting_layer).init();
+    registry()
+        .with(env_layer)
+        .with(formatting_layer)
+        .with(LogBufferLayer)
+        .init();
 }
 
 async fn stop_core() -> AppResult<()> {
     info!("stopping core");
     let venus = &mut global_core().await.lock().await;
-    venus.config.write_core()?;
-    venus.config.write_rua()?;
-    venus.kill_core()?;
+    // a file edited outside venus is kept, the core is stopped anyway
+    venus.config.write_core().map_err(log_err).ok();
+    venus.config.write_rua().map_err(log_err).ok();
+    venus.kill_core().await?;
     Ok(())
 }
 